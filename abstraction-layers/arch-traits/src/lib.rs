@@ -1,3 +1,9 @@
 #![no_std]
 #![deny(unsafe_code)]
 #![deny(clippy::all)]
+
+pub mod memory;
+pub mod paging;
+
+pub use memory::*;
+pub use paging::*;
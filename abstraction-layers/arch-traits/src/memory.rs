@@ -0,0 +1,49 @@
+pub const PAGE_SIZE: u64 = 4096;
+
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct PhysAddr(u64);
+
+impl PhysAddr {
+    pub const fn new(addr: u64) -> Self {
+        Self(addr)
+    }
+
+    pub const fn as_u64(self) -> u64 {
+        self.0
+    }
+
+    pub const fn align_down(self, align: u64) -> Self {
+        Self(self.0 & !(align - 1))
+    }
+
+    pub const fn align_up(self, align: u64) -> Self {
+        Self((self.0 + align - 1) & !(align - 1))
+    }
+}
+
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct PhysFrame(PhysAddr);
+
+impl PhysFrame {
+    pub const fn containing_address(addr: PhysAddr) -> Self {
+        Self(addr.align_down(PAGE_SIZE))
+    }
+
+    pub const fn from_number(number: u64) -> Self {
+        Self(PhysAddr::new(number * PAGE_SIZE))
+    }
+
+    pub const fn start_address(self) -> PhysAddr {
+        self.0
+    }
+
+    pub const fn number(self) -> u64 {
+        self.0.as_u64() / PAGE_SIZE
+    }
+}
+
+pub trait FrameAllocator {
+    fn allocate_frame(&mut self) -> Option<PhysFrame>;
+
+    fn deallocate_frame(&mut self, frame: PhysFrame);
+}
@@ -0,0 +1,99 @@
+use crate::{FrameAllocator, PhysAddr, PhysFrame, PAGE_SIZE};
+use core::ops::{BitOr, BitOrAssign};
+
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct VirtAddr(u64);
+
+impl VirtAddr {
+    pub const fn new(addr: u64) -> Self {
+        Self(addr)
+    }
+
+    pub const fn as_u64(self) -> u64 {
+        self.0
+    }
+
+    pub fn from_ptr<T>(ptr: *const T) -> Self {
+        Self(ptr as u64)
+    }
+
+    pub const fn as_mut_ptr<T>(self) -> *mut T {
+        self.0 as *mut T
+    }
+
+    pub const fn align_down(self, align: u64) -> Self {
+        Self(self.0 & !(align - 1))
+    }
+
+    pub const fn align_up(self, align: u64) -> Self {
+        Self((self.0 + align - 1) & !(align - 1))
+    }
+}
+
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct Page(VirtAddr);
+
+impl Page {
+    pub const fn containing_address(addr: VirtAddr) -> Self {
+        Self(addr.align_down(PAGE_SIZE))
+    }
+
+    pub const fn start_address(self) -> VirtAddr {
+        self.0
+    }
+
+    pub const fn offset(self, pages: u64) -> Self {
+        Self(VirtAddr::new(self.0.as_u64() + pages * PAGE_SIZE))
+    }
+}
+
+/// Access rights of a mapped page. Every mapped page is readable by the kernel.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct PageFlags(u8);
+
+impl PageFlags {
+    pub const NONE: Self = Self(0);
+    pub const WRITABLE: Self = Self(1 << 0);
+    pub const USER: Self = Self(1 << 1);
+    pub const NO_EXECUTE: Self = Self(1 << 2);
+    pub const NO_CACHE: Self = Self(1 << 3);
+    pub const WRITE_THROUGH: Self = Self(1 << 4);
+
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for PageFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for PageFlags {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MapError {
+    FrameAllocationFailed,
+    AlreadyMapped(PhysFrame),
+    NotMapped,
+    HugePage,
+    /// There is no room left in the range of virtual addresses being allocated from.
+    OutOfAddressSpace,
+}
+
+pub trait PageMapper {
+    fn map(&mut self, page: Page, frame: PhysFrame, flags: PageFlags, frames: &mut dyn FrameAllocator) -> Result<(), MapError>;
+
+    fn unmap(&mut self, page: Page) -> Result<PhysFrame, MapError>;
+
+    fn protect(&mut self, page: Page, flags: PageFlags) -> Result<(), MapError>;
+
+    fn translate(&self, addr: VirtAddr) -> Option<PhysAddr>;
+}
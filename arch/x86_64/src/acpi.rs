@@ -0,0 +1,159 @@
+use core::mem::size_of;
+use core::ptr;
+
+const MAX_IO_APICS: usize = 4;
+const ISA_IRQS: usize = 16;
+
+const SDT_HEADER_SIZE: u64 = 36;
+const MADT_ENTRIES_OFFSET: u64 = 44;
+
+const MADT_IO_APIC: u8 = 1;
+const MADT_INTERRUPT_OVERRIDE: u8 = 2;
+const MADT_LOCAL_APIC_OVERRIDE: u8 = 5;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct IoApicEntry {
+    pub id: u8,
+    pub address: u64,
+    /// The first global system interrupt the I/O APIC handles.
+    pub gsi_base: u32,
+}
+
+/// An ISA IRQ that isn't wired to the global system interrupt with the same number, or that
+/// doesn't use the ISA default of edge triggered and active high.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct InterruptOverride {
+    pub irq: u8,
+    pub gsi: u32,
+    pub active_low: bool,
+    pub level_triggered: bool,
+}
+
+/// What the MADT says about the interrupt controllers, limited to what is needed to route the
+/// ISA IRQs.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Madt {
+    pub local_apic_address: u64,
+    pub io_apics: [Option<IoApicEntry>; MAX_IO_APICS],
+    pub overrides: [Option<InterruptOverride>; ISA_IRQS],
+}
+
+impl Madt {
+    /// The I/O APIC that handles `gsi`.
+    pub fn io_apic_for(&self, gsi: u32) -> Option<IoApicEntry> {
+        self.io_apics.iter().flatten()
+            .filter(|io_apic| io_apic.gsi_base <= gsi)
+            .max_by_key(|io_apic| io_apic.gsi_base)
+            .copied()
+    }
+
+    /// Where ISA IRQ `irq` arrives, taking overrides into account.
+    pub fn isa_irq(&self, irq: u8) -> InterruptOverride {
+        self.overrides.get(usize::from(irq)).copied().flatten().unwrap_or(InterruptOverride {
+            irq,
+            gsi: u32::from(irq),
+            active_low: false,
+            level_triggered: false,
+        })
+    }
+}
+
+/// Reads physical memory through the region where all of it is mapped.
+struct PhysicalMemory {
+    offset: u64,
+}
+
+impl PhysicalMemory {
+    #[allow(unsafe_code)]
+    fn read<T: Copy>(&self, addr: u64) -> T {
+        unsafe { ptr::read_unaligned((self.offset + addr) as *const T) }
+    }
+
+    fn checksum_ok(&self, addr: u64, len: u64) -> bool {
+        (0..len).fold(0u8, |sum, i| sum.wrapping_add(self.read::<u8>(addr + i))) == 0
+    }
+}
+
+/// Finds the MADT through the RSDP the BIOS left in low memory.
+///
+/// # Safety
+/// All physical memory must be mapped at `physical_memory_offset`.
+#[allow(unsafe_code)]
+pub unsafe fn find_madt(physical_memory_offset: u64) -> Option<Madt> {
+    let memory = PhysicalMemory { offset: physical_memory_offset };
+    let rsdp = find_rsdp(&memory)?;
+    let revision: u8 = memory.read(rsdp + 15);
+
+    let (table, entry_size) = if revision >= 2 {
+        (memory.read::<u64>(rsdp + 24), size_of::<u64>() as u64)
+    } else {
+        (u64::from(memory.read::<u32>(rsdp + 16)), size_of::<u32>() as u64)
+    };
+    if !memory.checksum_ok(table, memory.read::<u32>(table + 4).into()) {
+        return None;
+    }
+
+    // A length shorter than the header means the table is corrupt, so act as if there is none.
+    let entries = u64::from(memory.read::<u32>(table + 4)).checked_sub(SDT_HEADER_SIZE)? / entry_size;
+    (0..entries)
+        .map(|i| {
+            let entry = table + SDT_HEADER_SIZE + i * entry_size;
+            if entry_size == 8 { memory.read::<u64>(entry) } else { u64::from(memory.read::<u32>(entry)) }
+        })
+        .find(|&sdt| memory.read::<[u8; 4]>(sdt) == *b"APIC" && memory.checksum_ok(sdt, memory.read::<u32>(sdt + 4).into()))
+        .map(|madt| parse_madt(&memory, madt))
+}
+
+fn find_rsdp(memory: &PhysicalMemory) -> Option<u64> {
+    let ebda = u64::from(memory.read::<u16>(0x40e)) << 4;
+    let is_rsdp = |addr: &u64| memory.read::<[u8; 8]>(*addr) == *b"RSD PTR " && memory.checksum_ok(*addr, 20);
+    (ebda..ebda + 1024).step_by(16).find(is_rsdp)
+        .or_else(|| (0xe0000..0x100000).step_by(16).find(is_rsdp))
+}
+
+fn parse_madt(memory: &PhysicalMemory, madt: u64) -> Madt {
+    let mut result = Madt {
+        local_apic_address: memory.read::<u32>(madt + SDT_HEADER_SIZE).into(),
+        io_apics: [None; MAX_IO_APICS],
+        overrides: [None; ISA_IRQS],
+    };
+
+    let end = madt + u64::from(memory.read::<u32>(madt + 4));
+    let mut entry = madt + MADT_ENTRIES_OFFSET;
+    while entry + 2 <= end {
+        let length: u8 = memory.read(entry + 1);
+        if length < 2 {
+            break;
+        }
+        match memory.read::<u8>(entry) {
+            MADT_IO_APIC => {
+                let io_apic = IoApicEntry {
+                    id: memory.read(entry + 2),
+                    address: memory.read::<u32>(entry + 4).into(),
+                    gsi_base: memory.read(entry + 8),
+                };
+                if let Some(slot) = result.io_apics.iter_mut().find(|slot| slot.is_none()) {
+                    *slot = Some(io_apic);
+                }
+            }
+            MADT_INTERRUPT_OVERRIDE => {
+                let irq: u8 = memory.read(entry + 3);
+                let flags: u16 = memory.read(entry + 8);
+                if let Some(slot) = result.overrides.get_mut(usize::from(irq)) {
+                    // Both fields are two bits wide, where 0b11 means active low or level triggered
+                    // and anything else keeps the ISA default.
+                    *slot = Some(InterruptOverride {
+                        irq,
+                        gsi: memory.read(entry + 4),
+                        active_low: flags & 0b11 == 0b11,
+                        level_triggered: (flags >> 2) & 0b11 == 0b11,
+                    });
+                }
+            }
+            MADT_LOCAL_APIC_OVERRIDE => result.local_apic_address = memory.read(entry + 4),
+            _ => {}
+        }
+        entry += u64::from(length);
+    }
+    result
+}
@@ -0,0 +1,210 @@
+use z_arch_traits::{PhysAddr, VirtAddr};
+use x86_64::registers::model_specific::Msr;
+use x86_64::instructions::port::Port;
+use crate::pit;
+
+const IA32_APIC_BASE: u32 = 0x1b;
+const APIC_BASE_ENABLE: u64 = 1 << 11;
+
+/// Size of the register block of either kind of APIC.
+pub const APIC_REGISTERS_SIZE: u64 = 0x1000;
+
+const LAPIC_ID: usize = 0x20;
+const LAPIC_TASK_PRIORITY: usize = 0x80;
+const LAPIC_EOI: usize = 0xb0;
+const LAPIC_SPURIOUS: usize = 0xf0;
+const LAPIC_IN_SERVICE: usize = 0x100;
+const LAPIC_LVT_TIMER: usize = 0x320;
+const LAPIC_TIMER_INITIAL: usize = 0x380;
+const LAPIC_TIMER_CURRENT: usize = 0x390;
+const LAPIC_TIMER_DIVIDE: usize = 0x3e0;
+
+const LAPIC_SOFTWARE_ENABLE: u32 = 1 << 8;
+const LVT_MASKED: u32 = 1 << 16;
+const LVT_TIMER_PERIODIC: u32 = 1 << 17;
+const TIMER_DIVIDE_BY_16: u32 = 0b0011;
+
+const IOAPIC_SELECT: usize = 0x00;
+const IOAPIC_WINDOW: usize = 0x10;
+const IOAPIC_VERSION: u32 = 0x01;
+const IOAPIC_REDIRECTION_TABLE: u32 = 0x10;
+
+const REDIRECTION_ACTIVE_LOW: u64 = 1 << 13;
+const REDIRECTION_LEVEL_TRIGGERED: u64 = 1 << 15;
+const REDIRECTION_MASKED: u64 = 1 << 16;
+
+/// Whether the CPU has a local APIC.
+#[allow(unsafe_code, unused_unsafe)]
+pub fn is_supported() -> bool {
+    let features = unsafe { core::arch::x86_64::__cpuid(1) };
+    features.edx & (1 << 9) != 0
+}
+
+/// Where the local APIC's registers are, according to the CPU.
+#[allow(unsafe_code)]
+pub fn local_apic_base() -> PhysAddr {
+    let base = unsafe { Msr::new(IA32_APIC_BASE).read() };
+    PhysAddr::new(base & 0x000f_ffff_ffff_f000)
+}
+
+/// Stops the 8259 PICs from raising interrupts. They should be remapped first, so any spurious
+/// interrupt they still send doesn't look like an exception.
+#[allow(unsafe_code)]
+pub fn disable_pics() {
+    unsafe {
+        Port::<u8>::new(0x21).write(0xff);
+        Port::<u8>::new(0xa1).write(0xff);
+    }
+}
+
+/// The interrupt controller built into the CPU.
+pub struct LocalApic {
+    base: VirtAddr,
+}
+
+impl LocalApic {
+    /// # Safety
+    /// `base` must be an uncached mapping of the local APIC's registers.
+    #[allow(unsafe_code)]
+    pub unsafe fn new(base: VirtAddr) -> Self {
+        Self { base }
+    }
+
+    #[allow(unsafe_code)]
+    fn read(&self, register: usize) -> u32 {
+        unsafe { self.base.as_mut_ptr::<u32>().byte_add(register).read_volatile() }
+    }
+
+    #[allow(unsafe_code)]
+    fn write(&self, register: usize, value: u32) {
+        unsafe { self.base.as_mut_ptr::<u32>().byte_add(register).write_volatile(value) }
+    }
+
+    /// Turns the APIC on, sending interrupts nobody raised to `spurious_vector`.
+    #[allow(unsafe_code)]
+    pub fn enable(&self, spurious_vector: u8) {
+        unsafe {
+            let mut base = Msr::new(IA32_APIC_BASE);
+            let value = base.read();
+            base.write(value | APIC_BASE_ENABLE);
+        }
+        self.write(LAPIC_TASK_PRIORITY, 0);
+        self.write(LAPIC_SPURIOUS, LAPIC_SOFTWARE_ENABLE | u32::from(spurious_vector));
+    }
+
+    pub fn id(&self) -> u8 {
+        (self.read(LAPIC_ID) >> 24) as u8
+    }
+
+    pub fn end_of_interrupt(&self) {
+        self.write(LAPIC_EOI, 0);
+    }
+
+    /// Whether the APIC is currently servicing `vector`.
+    pub fn is_in_service(&self, vector: u8) -> bool {
+        let register = LAPIC_IN_SERVICE + usize::from(vector / 32) * 0x10;
+        self.read(register) & (1 << (vector % 32)) != 0
+    }
+
+    /// Measures how many times a second the timer counts down, using the PIT as a reference.
+    pub fn timer_frequency(&self) -> u32 {
+        const SAMPLE_HZ: u32 = 100;
+        self.write(LAPIC_LVT_TIMER, LVT_MASKED);
+        self.write(LAPIC_TIMER_DIVIDE, TIMER_DIVIDE_BY_16);
+        self.write(LAPIC_TIMER_INITIAL, u32::MAX);
+        pit::wait(SAMPLE_HZ);
+        let elapsed = u32::MAX - self.read(LAPIC_TIMER_CURRENT);
+        self.write(LAPIC_TIMER_INITIAL, 0);
+        elapsed.saturating_mul(SAMPLE_HZ)
+    }
+
+    /// Raises `vector` `hz` times a second. `frequency` is the result of `timer_frequency`.
+    pub fn start_periodic_timer(&self, vector: u8, hz: u32, frequency: u32) {
+        self.write(LAPIC_TIMER_DIVIDE, TIMER_DIVIDE_BY_16);
+        self.write(LAPIC_LVT_TIMER, LVT_TIMER_PERIODIC | u32::from(vector));
+        self.write(LAPIC_TIMER_INITIAL, (frequency / hz.max(1)).max(1));
+    }
+
+    pub fn stop_timer(&self) {
+        self.write(LAPIC_LVT_TIMER, LVT_MASKED);
+        self.write(LAPIC_TIMER_INITIAL, 0);
+    }
+}
+
+/// Routes external interrupts to local APICs.
+pub struct IoApic {
+    base: VirtAddr,
+    gsi_base: u32,
+}
+
+/// How a global system interrupt is delivered.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Redirection {
+    pub vector: u8,
+    pub destination: u8,
+    pub active_low: bool,
+    pub level_triggered: bool,
+    pub masked: bool,
+}
+
+impl IoApic {
+    /// # Safety
+    /// `base` must be an uncached mapping of the I/O APIC's registers, and the I/O APIC must
+    /// handle the global system interrupts from `gsi_base` on.
+    #[allow(unsafe_code)]
+    pub unsafe fn new(base: VirtAddr, gsi_base: u32) -> Self {
+        Self { base, gsi_base }
+    }
+
+    #[allow(unsafe_code)]
+    fn read(&self, register: u32) -> u32 {
+        unsafe {
+            self.base.as_mut_ptr::<u32>().byte_add(IOAPIC_SELECT).write_volatile(register);
+            self.base.as_mut_ptr::<u32>().byte_add(IOAPIC_WINDOW).read_volatile()
+        }
+    }
+
+    #[allow(unsafe_code)]
+    fn write(&self, register: u32, value: u32) {
+        unsafe {
+            self.base.as_mut_ptr::<u32>().byte_add(IOAPIC_SELECT).write_volatile(register);
+            self.base.as_mut_ptr::<u32>().byte_add(IOAPIC_WINDOW).write_volatile(value);
+        }
+    }
+
+    /// The global system interrupts this I/O APIC handles.
+    pub fn gsis(&self) -> core::ops::Range<u32> {
+        let entries = (self.read(IOAPIC_VERSION) >> 16 & 0xff) + 1;
+        self.gsi_base..self.gsi_base + entries
+    }
+
+    /// Sets how `gsi` is delivered. Returns `false` if this I/O APIC doesn't handle it.
+    pub fn set_redirection(&self, gsi: u32, redirection: Redirection) -> bool {
+        if !self.gsis().contains(&gsi) {
+            return false;
+        }
+        let mut low = u64::from(redirection.vector);
+        if redirection.active_low {
+            low |= REDIRECTION_ACTIVE_LOW;
+        }
+        if redirection.level_triggered {
+            low |= REDIRECTION_LEVEL_TRIGGERED;
+        }
+        if redirection.masked {
+            low |= REDIRECTION_MASKED;
+        }
+        let register = IOAPIC_REDIRECTION_TABLE + (gsi - self.gsi_base) * 2;
+        // Mask the entry while it is half written.
+        self.write(register, REDIRECTION_MASKED as u32);
+        self.write(register + 1, u32::from(redirection.destination) << 24);
+        self.write(register, low as u32);
+        true
+    }
+
+    pub fn mask_all(&self) {
+        for gsi in self.gsis() {
+            let register = IOAPIC_REDIRECTION_TABLE + (gsi - self.gsi_base) * 2;
+            self.write(register, REDIRECTION_MASKED as u32);
+        }
+    }
+}
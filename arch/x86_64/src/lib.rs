@@ -3,3 +3,9 @@
 #![deny(clippy::all)]
 
 // TODO: Move/refactor the x86_64 specific code of ../../boot-bios into here
+
+pub mod acpi;
+pub mod apic;
+pub mod paging;
+pub mod pit;
+pub mod stack;
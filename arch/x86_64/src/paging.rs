@@ -0,0 +1,125 @@
+use z_arch_traits::{FrameAllocator, MapError, Page, PageFlags, PageMapper, PhysAddr, PhysFrame, VirtAddr};
+use x86_64::structures::paging::{
+    self as x86_paging, Mapper, OffsetPageTable, PageTable, PageTableFlags, Size4KiB, Translate,
+    mapper::{FlagUpdateError, MapToError, UnmapError},
+};
+use x86_64::registers::control::Cr3;
+
+/// Maps pages in the active level 4 table, reaching page tables through the region where the
+/// bootloader mapped all of physical memory.
+pub struct OffsetMapper {
+    physical_memory_offset: u64,
+    table: OffsetPageTable<'static>,
+}
+
+impl OffsetMapper {
+    /// # Safety
+    /// All physical memory must be mapped at `physical_memory_offset`, and only one mapper may
+    /// exist for the active page tables.
+    #[allow(unsafe_code)]
+    pub unsafe fn new(physical_memory_offset: u64) -> Self {
+        let offset = x86_64::VirtAddr::new(physical_memory_offset);
+        let (level_4_frame, _) = Cr3::read();
+        let level_4_table = offset + level_4_frame.start_address().as_u64();
+        let level_4_table = &mut *level_4_table.as_mut_ptr::<PageTable>();
+        Self { physical_memory_offset, table: OffsetPageTable::new(level_4_table, offset) }
+    }
+
+    /// The address where `addr` can be reached through the physical memory mapping.
+    pub fn phys_to_virt(&self, addr: PhysAddr) -> VirtAddr {
+        VirtAddr::new(self.physical_memory_offset + addr.as_u64())
+    }
+}
+
+fn to_page(page: Page) -> x86_paging::Page<Size4KiB> {
+    x86_paging::Page::containing_address(x86_64::VirtAddr::new(page.start_address().as_u64()))
+}
+
+fn to_frame(frame: PhysFrame) -> x86_paging::PhysFrame<Size4KiB> {
+    x86_paging::PhysFrame::containing_address(x86_64::PhysAddr::new(frame.start_address().as_u64()))
+}
+
+fn from_frame(frame: x86_paging::PhysFrame<Size4KiB>) -> PhysFrame {
+    PhysFrame::containing_address(PhysAddr::new(frame.start_address().as_u64()))
+}
+
+fn to_table_flags(flags: PageFlags) -> PageTableFlags {
+    let mut table_flags = PageTableFlags::PRESENT;
+    if flags.contains(PageFlags::WRITABLE) {
+        table_flags |= PageTableFlags::WRITABLE;
+    }
+    if flags.contains(PageFlags::USER) {
+        table_flags |= PageTableFlags::USER_ACCESSIBLE;
+    }
+    if flags.contains(PageFlags::NO_EXECUTE) {
+        table_flags |= PageTableFlags::NO_EXECUTE;
+    }
+    if flags.contains(PageFlags::NO_CACHE) {
+        table_flags |= PageTableFlags::NO_CACHE;
+    }
+    if flags.contains(PageFlags::WRITE_THROUGH) {
+        table_flags |= PageTableFlags::WRITE_THROUGH;
+    }
+    table_flags
+}
+
+/// Lets the x86_64 mapper take frames for new page tables from a kernel frame allocator.
+struct FrameSource<'a>(&'a mut dyn FrameAllocator);
+
+#[allow(unsafe_code)]
+unsafe impl x86_paging::FrameAllocator<Size4KiB> for FrameSource<'_> {
+    fn allocate_frame(&mut self) -> Option<x86_paging::PhysFrame<Size4KiB>> {
+        self.0.allocate_frame().map(to_frame)
+    }
+}
+
+impl PageMapper for OffsetMapper {
+    fn map(&mut self, page: Page, frame: PhysFrame, flags: PageFlags, frames: &mut dyn FrameAllocator) -> Result<(), MapError> {
+        let mut frames = FrameSource(frames);
+        // Intermediate tables must allow everything the page does, the leaf entry narrows it down.
+        let parent_flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE
+            | (to_table_flags(flags) & PageTableFlags::USER_ACCESSIBLE);
+        #[allow(unsafe_code)]
+        let result = unsafe {
+            self.table.map_to_with_table_flags(to_page(page), to_frame(frame), to_table_flags(flags), parent_flags, &mut frames)
+        };
+        match result {
+            Ok(flush) => {
+                flush.flush();
+                Ok(())
+            }
+            Err(MapToError::FrameAllocationFailed) => Err(MapError::FrameAllocationFailed),
+            Err(MapToError::ParentEntryHugePage) => Err(MapError::HugePage),
+            Err(MapToError::PageAlreadyMapped(frame)) => Err(MapError::AlreadyMapped(from_frame(frame))),
+        }
+    }
+
+    fn unmap(&mut self, page: Page) -> Result<PhysFrame, MapError> {
+        match self.table.unmap(to_page(page)) {
+            Ok((frame, flush)) => {
+                flush.flush();
+                Ok(from_frame(frame))
+            }
+            Err(UnmapError::ParentEntryHugePage) => Err(MapError::HugePage),
+            Err(UnmapError::PageNotMapped) | Err(UnmapError::InvalidFrameAddress(_)) => Err(MapError::NotMapped),
+        }
+    }
+
+    fn protect(&mut self, page: Page, flags: PageFlags) -> Result<(), MapError> {
+        #[allow(unsafe_code)]
+        let result = unsafe { self.table.update_flags(to_page(page), to_table_flags(flags)) };
+        match result {
+            Ok(flush) => {
+                flush.flush();
+                Ok(())
+            }
+            Err(FlagUpdateError::PageNotMapped) => Err(MapError::NotMapped),
+            Err(FlagUpdateError::ParentEntryHugePage) => Err(MapError::HugePage),
+        }
+    }
+
+    fn translate(&self, addr: VirtAddr) -> Option<PhysAddr> {
+        self.table.translate_addr(x86_64::VirtAddr::new(addr.as_u64()))
+            .map(|addr| PhysAddr::new(addr.as_u64()))
+    }
+}
@@ -0,0 +1,51 @@
+use x86_64::instructions::port::Port;
+
+/// The rate the PIT's counters count down at.
+pub const PIT_FREQUENCY: u32 = 1_193_182;
+
+const CHANNEL_0: u16 = 0x40;
+const CHANNEL_2: u16 = 0x42;
+const COMMAND: u16 = 0x43;
+/// Controls the channel 2 gate and reports its output.
+const SPEAKER_CONTROL: u16 = 0x61;
+
+fn divisor(hz: u32) -> u16 {
+    (PIT_FREQUENCY / hz.max(1)).clamp(1, u32::from(u16::MAX)) as u16
+}
+
+/// Makes channel 0, which raises IRQ 0, fire `hz` times a second.
+#[allow(unsafe_code)]
+pub fn set_frequency(hz: u32) {
+    let [low, high] = divisor(hz).to_le_bytes();
+    unsafe {
+        // Channel 0, low then high byte, square wave generator.
+        Port::<u8>::new(COMMAND).write(0b0011_0110);
+        let mut data = Port::<u8>::new(CHANNEL_0);
+        data.write(low);
+        data.write(high);
+    }
+}
+
+/// Busy waits for `1 / hz` seconds using channel 2, which isn't connected to an interrupt.
+#[allow(unsafe_code)]
+pub fn wait(hz: u32) {
+    let [low, high] = divisor(hz).to_le_bytes();
+    unsafe {
+        let mut control = Port::<u8>::new(SPEAKER_CONTROL);
+        // Open the gate but keep the speaker off.
+        let value = control.read();
+        control.write((value & !0b10) | 0b01);
+
+        // Channel 2, low then high byte, interrupt on terminal count.
+        Port::<u8>::new(COMMAND).write(0b1011_0000);
+        let mut data = Port::<u8>::new(CHANNEL_2);
+        data.write(low);
+        data.write(high);
+
+        // Pulse the gate to start counting, then wait for the output to go high.
+        let value = control.read();
+        control.write(value & !0b01);
+        control.write(value | 0b01);
+        while control.read() & 0b10_0000 == 0 {}
+    }
+}
@@ -0,0 +1,17 @@
+use z_arch_traits::VirtAddr;
+
+/// Moves onto the stack ending at `top` and calls `entry`. The old stack is abandoned.
+///
+/// # Safety
+/// `top` must be the 16 byte aligned end of a mapped stack that nothing else is using.
+#[allow(unsafe_code)]
+pub unsafe fn switch_stack(top: VirtAddr, entry: extern "C" fn() -> !) -> ! {
+    core::arch::asm!(
+        "mov rsp, {top}",
+        "xor ebp, ebp",
+        "call {entry}",
+        top = in(reg) top.as_u64(),
+        entry = in(reg) entry,
+        options(noreturn),
+    )
+}
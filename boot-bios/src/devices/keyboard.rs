@@ -1,15 +1,24 @@
-use crate::interrupts::{InterruptIndex, self};
+use alloc::boxed::Box;
+use kernel::allocators::INTERRUPTS_ARENA;
+use kernel::interrupts::{self, HandlerResult, Interrupt, Priority};
 use crate::event_loop;
-use x86_64::structures::idt::InterruptStackFrame;
 use x86_64::instructions::port::Port;
 use kernel::Event;
 
 pub fn init() {
-    interrupts::set_interrupt_handler(InterruptIndex::Keyboard, keyboard_handler);
+    interrupts::set_handler(Interrupt::KEYBOARD, Priority::DRIVER, Box::new_in(keyboard_handler, &INTERRUPTS_ARENA));
 }
 
-fn keyboard_handler(_stack_frame: InterruptStackFrame) {
+fn keyboard_handler(_interrupt: Interrupt) -> HandlerResult {
     let mut port = Port::new(0x60);
     let scancode: u8 = unsafe { port.read() };
-    unsafe { event_loop::EVENT_LOOP.emit_event(Event::Keyboard(scancode)); }
+    kernel::task::keyboard::push_scancode(scancode);
+    // The scancode has already been read, so it is lost either way if the queue is full.
+    let _ = interrupts::defer(emit_scancode, usize::from(scancode));
+    HandlerResult::Claim
+}
+
+fn emit_scancode(scancode: usize) {
+    // Dropped keystrokes are counted by the event loop.
+    let _ = event_loop::EVENT_LOOP.emit_event(Event::Keyboard(scancode as u8));
 }
@@ -1,2 +1,3 @@
 pub mod timer;
 pub mod keyboard;
+pub mod serial;
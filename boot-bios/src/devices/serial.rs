@@ -0,0 +1,54 @@
+use core::fmt;
+use alloc::boxed::Box;
+use x86_64::instructions::port::Port;
+
+const COM1: u16 = 0x3F8;
+
+pub struct SerialPort {
+    data: Port<u8>,
+    line_status: Port<u8>,
+}
+
+impl SerialPort {
+    unsafe fn new(base: u16) -> Self {
+        let mut interrupt_enable = Port::<u8>::new(base + 1);
+        let mut fifo_control = Port::<u8>::new(base + 2);
+        let mut line_control = Port::<u8>::new(base + 3);
+        let mut modem_control = Port::<u8>::new(base + 4);
+        let mut data = Port::<u8>::new(base);
+
+        interrupt_enable.write(0x00);
+        // 38400 baud, 8 data bits, no parity, one stop bit
+        line_control.write(0x80);
+        data.write(0x03);
+        interrupt_enable.write(0x00);
+        line_control.write(0x03);
+        fifo_control.write(0xC7);
+        modem_control.write(0x0B);
+
+        Self { data, line_status: Port::new(base + 5) }
+    }
+
+    fn write_byte(&mut self, byte: u8) {
+        unsafe {
+            while self.line_status.read() & 0x20 == 0 {
+                core::hint::spin_loop();
+            }
+            self.data.write(byte);
+        }
+    }
+}
+
+impl fmt::Write for SerialPort {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            self.write_byte(byte);
+        }
+        Ok(())
+    }
+}
+
+pub fn init() {
+    let port = unsafe { SerialPort::new(COM1) };
+    kernel::console::set_console(Box::new(port));
+}
@@ -1,18 +1,20 @@
-use x86_64::structures::idt::InterruptStackFrame;
-use crate::interrupts::{InterruptIndex, self};
+use alloc::boxed::Box;
+use kernel::allocators::INTERRUPTS_ARENA;
+use kernel::interrupts::{self, HandlerResult, Interrupt, Priority};
 use crate::event_loop;
 
-static mut TICKS: usize = 0;
-
 pub fn init() {
-    interrupts::set_interrupt_handler(InterruptIndex::Timer, timer_handler);
+    kernel::time::set_tick_frequency(crate::interrupts::TIMER_FREQUENCY);
+    interrupts::set_handler(Interrupt::TIMER, Priority::DRIVER, Box::new_in(timer_handler, &INTERRUPTS_ARENA));
+}
+
+fn timer_handler(_interrupt: Interrupt) -> HandlerResult {
+    kernel::time::tick();
+    // If the queue is full the loop is already due to be polled.
+    let _ = interrupts::defer(poll_event_loop, 0);
+    HandlerResult::Claim
 }
 
-fn timer_handler(_stack_frame: InterruptStackFrame) {
-    x86_64::instructions::interrupts::without_interrupts(|| {
-        unsafe {
-            TICKS += 1;
-            event_loop::EVENT_LOOP.poll();
-        }
-    });
+fn poll_event_loop(_: usize) {
+    event_loop::EVENT_LOOP.poll();
 }
@@ -2,9 +2,19 @@ use x86_64::structures::tss::TaskStateSegment;
 use x86_64::structures::gdt::{GlobalDescriptorTable, Descriptor};
 use x86_64::VirtAddr;
 use x86_64::instructions::segmentation::{CS, Segment};
+use x86_64::instructions::tables::load_tss;
+use kernel::memory;
 
 pub const DOUBLE_FAULT_IST_INDEX: u16 = 0;
 
+const IST_STACK_PAGES: u64 = 5;
+
+fn ist_stack(name: &'static str) -> VirtAddr {
+    let stack = memory::allocate_stack(name, IST_STACK_PAGES)
+        .unwrap_or_else(|err| panic!("Failed to allocate {} stack: {:?}", name, err));
+    VirtAddr::new(stack.top().as_u64())
+}
+
 fn init_tss() -> &'static mut TaskStateSegment {
     static mut TSS: Option<TaskStateSegment> = None;
     let tss = unsafe {
@@ -15,12 +25,7 @@ fn init_tss() -> &'static mut TaskStateSegment {
         TSS.as_mut().unwrap()
     };
 
-    tss.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize] = {
-        const STACK_SIZE: usize = 4096 * 5;
-        static mut STACK: [u8; STACK_SIZE] = [0; STACK_SIZE];
-        let stack_start = VirtAddr::from_ptr(unsafe { &STACK });
-        stack_start + STACK_SIZE
-    };
+    tss.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize] = ist_stack("double fault");
 
     tss
 }
@@ -43,6 +48,7 @@ pub fn init() {
     gdt.load();
     unsafe {
         CS::set_reg(code_selector);
-        CS::set_reg(tss_selector);
+        // The IST stacks only take effect once the TSS is loaded.
+        load_tss(tss_selector);
     };
 }
@@ -1,4 +1,7 @@
-use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame};
+use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame, PageFaultErrorCode};
+use x86_64::registers::control::Cr2;
+use kernel::interrupts::{self, Exception, ExceptionAction, ExceptionContext};
+use kernel::memory::{self, PageFaultCause, VirtAddr};
 
 pub fn init() -> &'static mut InterruptDescriptorTable {
     static mut IDT: Option<InterruptDescriptorTable> = None;
@@ -10,8 +13,31 @@ pub fn init() -> &'static mut InterruptDescriptorTable {
         IDT.as_mut().unwrap()
     };
 
+    idt.divide_error.set_handler_fn(divide_error_handler);
+    idt.debug.set_handler_fn(debug_handler);
+    idt.non_maskable_interrupt.set_handler_fn(non_maskable_interrupt_handler);
     idt.breakpoint.set_handler_fn(breakpoint_handler);
+    idt.overflow.set_handler_fn(overflow_handler);
+    idt.bound_range_exceeded.set_handler_fn(bound_range_exceeded_handler);
+    idt.invalid_opcode.set_handler_fn(invalid_opcode_handler);
+    idt.device_not_available.set_handler_fn(device_not_available_handler);
+    idt.invalid_tss.set_handler_fn(invalid_tss_handler);
+    idt.segment_not_present.set_handler_fn(segment_not_present_handler);
+    idt.stack_segment_fault.set_handler_fn(stack_segment_fault_handler);
+    idt.general_protection_fault.set_handler_fn(general_protection_fault_handler);
+    idt.x87_floating_point.set_handler_fn(x87_floating_point_handler);
+    idt.alignment_check.set_handler_fn(alignment_check_handler);
+    idt.machine_check.set_handler_fn(machine_check_handler);
+    idt.simd_floating_point.set_handler_fn(simd_floating_point_handler);
+    idt.virtualization.set_handler_fn(virtualization_handler);
+    idt.cp_protection_exception.set_handler_fn(control_protection_handler);
+    idt.hv_injection_exception.set_handler_fn(hypervisor_injection_handler);
+    idt.vmm_communication_exception.set_handler_fn(vmm_communication_handler);
+    idt.security_exception.set_handler_fn(security_exception_handler);
 
+    // Page faults stay on the current stack so they can nest, e.g. when a resolver touches a page
+    // that isn't mapped yet. Running into a guard page turns into a double fault instead.
+    idt.page_fault.set_handler_fn(page_fault_handler);
     unsafe {
         idt.double_fault.set_handler_fn(double_fault_handler)
             .set_stack_index(crate::gdt::DOUBLE_FAULT_IST_INDEX);
@@ -20,10 +46,109 @@ pub fn init() -> &'static mut InterruptDescriptorTable {
     idt
 }
 
-extern "x86-interrupt" fn breakpoint_handler(_stack_frame: InterruptStackFrame) {
-    // println!("EXCEPTION: BREAKPOINT\n{:#?}", stack_frame);
+/// Asks the handlers registered with the kernel what to do about `exception`, doing `default` if
+/// none of them decides, and panics if that's what is wanted. Returns when the faulting code can
+/// be resumed.
+fn handle(stack_frame: &InterruptStackFrame, exception: Exception, default: ExceptionAction) {
+    match dispatch(stack_frame, exception).unwrap_or(default) {
+        ExceptionAction::Continue | ExceptionAction::Resume => {}
+        action => fail(stack_frame, exception, action),
+    }
+}
+
+fn dispatch(stack_frame: &InterruptStackFrame, exception: Exception) -> Option<ExceptionAction> {
+    let context = ExceptionContext {
+        exception,
+        instruction_pointer: stack_frame.instruction_pointer.as_u64(),
+        stack_pointer: stack_frame.stack_pointer.as_u64(),
+    };
+    interrupts::emit_exception(&context)
+}
+
+fn fail(stack_frame: &InterruptStackFrame, exception: Exception, action: ExceptionAction) -> ! {
+    match action {
+        // There are no tasks yet, so the only thing running is the kernel itself.
+        ExceptionAction::KillTask => panic!("EXCEPTION: {:?} in a task, but there is no task to kill\n{:#?}", exception, stack_frame),
+        _ => panic!("EXCEPTION: {:?}\n{:#?}", exception, stack_frame),
+    }
+}
+
+macro_rules! exception_handler {
+    ($name:ident, $exception:ident) => {
+        extern "x86-interrupt" fn $name(stack_frame: InterruptStackFrame) {
+            handle(&stack_frame, Exception::$exception, ExceptionAction::Panic);
+        }
+    };
+    ($name:ident, $exception:ident, error_code) => {
+        extern "x86-interrupt" fn $name(stack_frame: InterruptStackFrame, error_code: u64) {
+            handle(&stack_frame, Exception::$exception { error_code }, ExceptionAction::Panic);
+        }
+    };
+}
+
+exception_handler!(divide_error_handler, DivideByZero);
+exception_handler!(debug_handler, Debug);
+exception_handler!(non_maskable_interrupt_handler, NonMaskableInterrupt);
+exception_handler!(overflow_handler, Overflow);
+exception_handler!(bound_range_exceeded_handler, BoundRangeExceeded);
+exception_handler!(invalid_opcode_handler, InvalidOpcode);
+exception_handler!(device_not_available_handler, DeviceNotAvailable);
+exception_handler!(invalid_tss_handler, InvalidTss, error_code);
+exception_handler!(segment_not_present_handler, SegmentNotPresent, error_code);
+exception_handler!(stack_segment_fault_handler, StackSegmentFault, error_code);
+exception_handler!(general_protection_fault_handler, GeneralProtectionFault, error_code);
+exception_handler!(x87_floating_point_handler, X87FloatingPoint);
+exception_handler!(alignment_check_handler, AlignmentCheck, error_code);
+exception_handler!(simd_floating_point_handler, SimdFloatingPoint);
+exception_handler!(virtualization_handler, Virtualization);
+exception_handler!(control_protection_handler, ControlProtection, error_code);
+exception_handler!(hypervisor_injection_handler, HypervisorInjection);
+exception_handler!(vmm_communication_handler, VmmCommunication, error_code);
+exception_handler!(security_exception_handler, Security, error_code);
+
+extern "x86-interrupt" fn breakpoint_handler(stack_frame: InterruptStackFrame) {
+    // A breakpoint nobody is listening for is ignored rather than treated as a crash.
+    handle(&stack_frame, Exception::Breakpoint, ExceptionAction::Resume);
+}
+
+fn check_stack_overflow(address: u64) {
+    if let Some(stack) = memory::stack_for_guard(VirtAddr::new(address)) {
+        panic!("Stack overflow in {} stack", stack.name());
+    }
 }
 
 extern "x86-interrupt" fn double_fault_handler(stack_frame: InterruptStackFrame, _error_code: u64) -> ! {
-    panic!("EXCEPTION: DOUBLE FAULT\n{:#?}", stack_frame);
+    // A fault while pushing onto an overflowed stack escalates straight to a double fault.
+    check_stack_overflow(Cr2::read().as_u64());
+    // Handlers get to see it, but there is no way back from a double fault.
+    let action = dispatch(&stack_frame, Exception::DoubleFault)
+        .filter(|&action| action != ExceptionAction::Resume)
+        .unwrap_or(ExceptionAction::Panic);
+    fail(&stack_frame, Exception::DoubleFault, action)
+}
+
+extern "x86-interrupt" fn machine_check_handler(stack_frame: InterruptStackFrame) -> ! {
+    // Like a double fault, the state the CPU was in can't be trusted enough to return to.
+    let action = dispatch(&stack_frame, Exception::MachineCheck)
+        .filter(|&action| action != ExceptionAction::Resume)
+        .unwrap_or(ExceptionAction::Panic);
+    fail(&stack_frame, Exception::MachineCheck, action)
+}
+
+extern "x86-interrupt" fn page_fault_handler(stack_frame: InterruptStackFrame, error_code: PageFaultErrorCode) {
+    let address = Cr2::read().as_u64();
+    check_stack_overflow(address);
+
+    let cause = PageFaultCause::from_error_code(error_code.bits());
+    if memory::resolve_page_fault(VirtAddr::new(address), cause) {
+        return;
+    }
+    let exception = Exception::PageFault { address, error_code: error_code.bits() };
+    match dispatch(&stack_frame, exception).unwrap_or(ExceptionAction::Panic) {
+        ExceptionAction::Continue | ExceptionAction::Resume => {}
+        action => panic!(
+            "EXCEPTION: PAGE FAULT: {} at {:#x}, instruction at {:#x} ({:?})\n{:#?}",
+            cause, address, stack_frame.instruction_pointer.as_u64(), action, stack_frame,
+        ),
+    }
 }
@@ -1,8 +1,17 @@
 use x86_64::structures::idt::{InterruptStackFrame, InterruptDescriptorTable};
+use kernel::interrupts::{self, Interrupt, INTERRUPT_COUNT};
+use kernel::memory::{self, PhysAddr};
+use z_x86_64::{acpi, apic, pit};
+use z_x86_64::apic::{IoApic, LocalApic, Redirection};
+use spin::Once;
+
+/// How often the timer interrupt fires, whichever controller raises it.
+pub const TIMER_FREQUENCY: u32 = 100;
 
 pub(in crate::interrupts) mod pic {
     use pic8259::ChainedPics;
     use spin::Mutex;
+    use x86_64::instructions::port::Port;
 
     pub const PIC_1_OFFSET: u8 = 32;
     pub const PIC_2_OFFSET: u8 = PIC_1_OFFSET + 8;
@@ -10,64 +19,167 @@ pub(in crate::interrupts) mod pic {
     pub static PICS: Mutex<ChainedPics> =
         spin::Mutex::new(unsafe { ChainedPics::new(PIC_1_OFFSET, PIC_2_OFFSET) });
 
+    const PIC_1_COMMAND: u16 = 0x20;
+    const PIC_2_COMMAND: u16 = 0xa0;
+    const READ_IN_SERVICE: u8 = 0x0b;
+    const END_OF_INTERRUPT: u8 = 0x20;
+
     pub fn init() {
         unsafe { PICS.lock().initialize() };
     }
+
+    /// Whether the PIC is servicing `irq`. A PIC raises IRQ 7 or 15 without setting its bit when
+    /// the line it was asked about went quiet before the CPU acknowledged it.
+    pub fn is_in_service(irq: u8) -> bool {
+        let _pics = PICS.lock();
+        let mut command = Port::<u8>::new(if irq < 8 { PIC_1_COMMAND } else { PIC_2_COMMAND });
+        unsafe {
+            command.write(READ_IN_SERVICE);
+            command.read() & (1 << (irq % 8)) != 0
+        }
+    }
+
+    /// Acknowledges the cascade on the master only, for a spurious IRQ from the slave.
+    pub fn end_of_interrupt_master() {
+        let _pics = PICS.lock();
+        unsafe { Port::<u8>::new(PIC_1_COMMAND).write(END_OF_INTERRUPT) };
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
-#[repr(u8)]
-pub enum InterruptIndex {
-    Timer = pic::PIC_1_OFFSET,
-    Keyboard,
+enum Controller {
+    Pic,
+    Apic {
+        local: LocalApic,
+    },
 }
 
-impl InterruptIndex {
-    fn as_u8(self) -> u8 {
-        self as u8
-    }
+static CONTROLLER: Once<Controller> = Once::new();
 
-    fn as_usize(self) -> usize {
-        usize::from(self.as_u8())
-    }
+/// The IDT vector `interrupt` arrives on. Legacy IRQs follow the PIC offset and every other line
+/// comes after them.
+pub fn vector(interrupt: Interrupt) -> u8 {
+    pic::PIC_1_OFFSET + interrupt.number() as u8
 }
 
 pub fn init(idt: &mut InterruptDescriptorTable) {
+    // Remap the PICs even when they won't be used, so whatever they still send doesn't land on
+    // an exception vector.
     pic::init();
-    idt[InterruptIndex::Timer.as_usize()].set_handler_fn(timer_interrupt_handler);
-    idt[InterruptIndex::Keyboard.as_usize()].set_handler_fn(keyboard_interrupt_handler);
+
+    // Every line gets an entry, including those `allocate_vector` hands out for MSIs and other
+    // interrupts that are routed at runtime.
+    for (line, handler) in LINE_HANDLERS.iter().flatten().enumerate() {
+        let interrupt = Interrupt::from_number(line).unwrap();
+        idt[usize::from(vector(interrupt))].set_handler_fn(*handler);
+    }
+
+    let controller = init_apic().unwrap_or_else(|| {
+        pit::set_frequency(TIMER_FREQUENCY);
+        Controller::Pic
+    });
+    if let Controller::Apic { .. } = controller {
+        idt[usize::from(vector(Interrupt::SPURIOUS))].set_handler_fn(apic_spurious_handler);
+    }
+    CONTROLLER.call_once(|| controller);
 }
 
-static mut EXTERNAL_HANDLERS: [Option<fn(InterruptStackFrame)>; 2] = [None; 2];
+/// Switches from the PICs to the APICs if the CPU has a local APIC and ACPI describes an I/O
+/// APIC for the ISA IRQs.
+fn init_apic() -> Option<Controller> {
+    if !apic::is_supported() {
+        return None;
+    }
+    let madt = unsafe { acpi::find_madt(crate::memory::physical_memory_offset()) }?;
+    madt.io_apic_for(0)?;
+
+    let map_registers = |addr| memory::map_mmio(PhysAddr::new(addr), apic::APIC_REGISTERS_SIZE).ok();
+    let local = unsafe { LocalApic::new(map_registers(apic::local_apic_base().as_u64())?) };
+    let io_apics = madt.io_apics.map(|entry| {
+        let entry = entry?;
+        Some(unsafe { IoApic::new(map_registers(entry.address)?, entry.gsi_base) })
+    });
 
-pub fn set_interrupt_handler(index: InterruptIndex, handler: fn(InterruptStackFrame)) {
-    let index = usize::from(index.as_u8() - pic::PIC_1_OFFSET);
-    unsafe {
-        if EXTERNAL_HANDLERS[index].is_some() {
-            panic!("Interrupt index {} already has a handler", index);
+    apic::disable_pics();
+    local.enable(vector(Interrupt::SPURIOUS));
+    io_apics.iter().flatten().for_each(IoApic::mask_all);
+
+    // The local APIC timer replaces the PIT, so IRQ 0 stays masked. IRQ 2 only connects the
+    // PICs to each other.
+    for irq in (0..16).filter(|&irq| irq != 0 && irq != 2) {
+        let interrupt = Interrupt::irq(irq).unwrap();
+        let route = madt.isa_irq(irq);
+        let redirection = Redirection {
+            vector: vector(interrupt),
+            destination: local.id(),
+            active_low: route.active_low,
+            level_triggered: route.level_triggered,
+            masked: false,
+        };
+        if !io_apics.iter().flatten().any(|io_apic| io_apic.set_redirection(route.gsi, redirection)) {
+            kernel::println!("IRQ {} is wired to GSI {}, which no I/O APIC handles", irq, route.gsi);
         }
-        EXTERNAL_HANDLERS[index] = Some(handler);
     }
+
+    let frequency = local.timer_frequency();
+    local.start_periodic_timer(vector(Interrupt::TIMER), TIMER_FREQUENCY, frequency);
+    Some(Controller::Apic { local })
 }
 
-extern "x86-interrupt" fn timer_interrupt_handler(stack_frame: InterruptStackFrame) {
-    unsafe {
-        let index = usize::from(InterruptIndex::Timer.as_u8() - pic::PIC_1_OFFSET);
-        if let Some(handler) = EXTERNAL_HANDLERS[index] {
-            handler(stack_frame);
-        }
-        pic::PICS.lock()
-            .notify_end_of_interrupt(InterruptIndex::Timer.as_u8());
+fn end_of_interrupt(interrupt: Interrupt) {
+    match CONTROLLER.get() {
+        Some(Controller::Apic { local, .. }) => local.end_of_interrupt(),
+        _ => unsafe {
+            pic::PICS.lock()
+                .notify_end_of_interrupt(vector(interrupt));
+        },
+    }
+}
+
+/// Whether an interrupt on IRQ 7 or 15 came from a PIC without any device asking for it.
+fn is_spurious(interrupt: Interrupt) -> bool {
+    if interrupt != Interrupt::LPT1 && interrupt != Interrupt::SECONDARY_ATA {
+        return false;
+    }
+    match CONTROLLER.get() {
+        // A masked PIC can still send these, but then the local APIC isn't servicing them.
+        Some(Controller::Apic { local, .. }) => !local.is_in_service(vector(interrupt)),
+        _ => !pic::is_in_service(interrupt.number() as u8),
     }
 }
 
-extern "x86-interrupt" fn keyboard_interrupt_handler(stack_frame: InterruptStackFrame) {
-    unsafe {
-        let index = usize::from(InterruptIndex::Keyboard.as_u8() - pic::PIC_1_OFFSET);
-        if let Some(handler) = EXTERNAL_HANDLERS[index] {
-            handler(stack_frame);
+/// Passes an interrupt on to the handlers registered with the kernel.
+fn dispatch(interrupt: Interrupt) {
+    if is_spurious(interrupt) {
+        interrupts::record_spurious(interrupt);
+        // The slave didn't expect an EOI, but the master saw a real interrupt on the cascade.
+        // When the APICs are in use the PICs are masked, and the master saw nothing.
+        if interrupt == Interrupt::SECONDARY_ATA && matches!(CONTROLLER.get(), None | Some(Controller::Pic)) {
+            pic::end_of_interrupt_master();
         }
-        pic::PICS.lock()
-            .notify_end_of_interrupt(InterruptIndex::Keyboard.as_u8());
+        return;
     }
+    interrupts::emit_interrupt(interrupt);
+    end_of_interrupt(interrupt);
+}
+
+extern "x86-interrupt" fn line_handler<const LINE: usize>(_stack_frame: InterruptStackFrame) {
+    dispatch(Interrupt::from_number(LINE).unwrap());
+}
+
+macro_rules! line_handlers {
+    (@row $row:literal $($column:literal)*) => {
+        [$(line_handler::<{ $row * 16 + $column }>),*]
+    };
+    ($($row:literal)*) => {
+        [$(line_handlers!(@row $row 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15)),*]
+    };
+}
+
+/// A handler for each line, in rows of 16.
+const LINE_HANDLERS: [[extern "x86-interrupt" fn(InterruptStackFrame); 16]; INTERRUPT_COUNT / 16] =
+    line_handlers!(0 1 2 3 4 5 6 7 8 9 10 11 12 13);
+
+/// Spurious interrupts from the local APIC must not be acknowledged.
+extern "x86-interrupt" fn apic_spurious_handler(_stack_frame: InterruptStackFrame) {
+    interrupts::record_spurious(Interrupt::SPURIOUS);
 }
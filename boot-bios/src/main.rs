@@ -1,24 +1,42 @@
 #![no_std]
 #![no_main]
 #![feature(abi_x86_interrupt)]
+#![feature(allocator_api)]
 
 extern crate rlibc;
+extern crate alloc;
 
 mod idt;
 mod gdt;
 mod interrupts;
 mod devices;
 mod event_loop;
+mod memory;
 
 use core::panic::PanicInfo;
+use bootloader::{BootInfo, entry_point};
+
+const KERNEL_STACK_PAGES: u64 = 32;
 
 #[panic_handler]
-fn panic(_info: &PanicInfo) -> ! {
+fn panic(info: &PanicInfo) -> ! {
+    kernel::println!("{}", info);
     loop {}
 }
 
-#[no_mangle]
-pub extern "C" fn _start() -> ! {
+entry_point!(kernel_main);
+
+fn kernel_main(boot_info: &'static BootInfo) -> ! {
+    memory::init(boot_info);
+    devices::serial::init();
+
+    // Leave the bootloader's stack for one with a guard page we know about.
+    let stack = kernel::memory::allocate_stack("kernel", KERNEL_STACK_PAGES)
+        .expect("Failed to allocate the kernel stack");
+    unsafe { z_x86_64::stack::switch_stack(stack.top(), kernel_start) }
+}
+
+extern "C" fn kernel_start() -> ! {
     {
         gdt::init();
         let idt = idt::init();
@@ -31,5 +49,16 @@ pub extern "C" fn _start() -> ! {
     }
 
     x86_64::instructions::interrupts::enable();
-    loop { x86_64::instructions::hlt() };
+    loop {
+        kernel::interrupts::run_deferred();
+        kernel::task::run_ready();
+        // Checking for work and halting have to happen with interrupts off, otherwise work queued
+        // in between would wait for the next interrupt.
+        x86_64::instructions::interrupts::disable();
+        if kernel::interrupts::has_deferred_work() || kernel::task::has_ready_tasks() {
+            x86_64::instructions::interrupts::enable();
+        } else {
+            x86_64::instructions::interrupts::enable_and_hlt();
+        }
+    }
 }
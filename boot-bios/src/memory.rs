@@ -0,0 +1,35 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+use bootloader::BootInfo;
+use bootloader::bootinfo::MemoryRegionType;
+use kernel::memory::{self, MemoryRegion, PhysAddr};
+use kernel::allocators;
+use z_x86_64::paging::OffsetMapper;
+
+// Enough to track the first 4 GiB of physical memory
+const MAX_PHYSICAL_ADDRESS: u64 = 4 * 1024 * 1024 * 1024;
+const FRAME_BITMAP_WORDS: usize = memory::bitmap_words(MAX_PHYSICAL_ADDRESS);
+
+static PHYSICAL_MEMORY_OFFSET: AtomicU64 = AtomicU64::new(0);
+
+/// Where the bootloader mapped all of physical memory.
+pub fn physical_memory_offset() -> u64 {
+    PHYSICAL_MEMORY_OFFSET.load(Ordering::Relaxed)
+}
+
+pub fn init(boot_info: &'static BootInfo) {
+    static mut FRAME_BITMAP: [u64; FRAME_BITMAP_WORDS] = [0; FRAME_BITMAP_WORDS];
+
+    let regions = boot_info.memory_map.iter()
+        .filter(|region| region.region_type == MemoryRegionType::Usable)
+        .map(|region| MemoryRegion::new(
+            PhysAddr::new(region.range.start_addr()),
+            PhysAddr::new(region.range.end_addr()),
+        ));
+    memory::init_frame_allocator(regions, unsafe { &mut FRAME_BITMAP });
+
+    PHYSICAL_MEMORY_OFFSET.store(boot_info.physical_memory_offset, Ordering::Relaxed);
+    let mapper = unsafe { OffsetMapper::new(boot_info.physical_memory_offset) };
+    memory::init_page_mapper(mapper);
+
+    allocators::init_heap(allocators::DEFAULT_HEAP_LIMIT);
+}
@@ -0,0 +1,61 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use core::alloc::Layout;
+use core::ptr::NonNull;
+use libfuzzer_sys::fuzz_target;
+use z_core::allocators::FreeListHeap;
+
+const HEAP_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Arbitrary)]
+enum Op {
+    Allocate { size: u16, align_shift: u8 },
+    Deallocate { index: u8 },
+}
+
+struct Live {
+    ptr: NonNull<u8>,
+    layout: Layout,
+}
+
+fuzz_target!(|ops: Vec<Op>| {
+    let mut memory = vec![0u8; HEAP_SIZE];
+    let heap_start = memory.as_mut_ptr() as usize;
+    let mut heap = FreeListHeap::empty();
+    unsafe { heap.add_region(heap_start, HEAP_SIZE) };
+    let total = heap.free_bytes();
+
+    let mut live: Vec<Live> = Vec::new();
+    for op in ops {
+        match op {
+            Op::Allocate { size, align_shift } => {
+                let layout = Layout::from_size_align(size as usize, 1 << (align_shift % 12)).unwrap();
+                if let Some(ptr) = heap.allocate(layout) {
+                    let start = ptr.as_ptr() as usize;
+                    let end = start + FreeListHeap::block_size(layout);
+                    assert_eq!(start % layout.align(), 0);
+                    assert!(start >= heap_start && end <= heap_start + HEAP_SIZE);
+                    for other in &live {
+                        let other_start = other.ptr.as_ptr() as usize;
+                        let other_end = other_start + FreeListHeap::block_size(other.layout);
+                        assert!(end <= other_start || other_end <= start);
+                    }
+                    live.push(Live { ptr, layout });
+                }
+            }
+            Op::Deallocate { index } if !live.is_empty() => {
+                let entry = live.swap_remove(index as usize % live.len());
+                unsafe { heap.deallocate(entry.ptr, entry.layout) };
+            }
+            Op::Deallocate { .. } => {}
+        }
+    }
+
+    for entry in live {
+        unsafe { heap.deallocate(entry.ptr, entry.layout) };
+    }
+    // Everything was freed, so it must have coalesced back into a single block.
+    assert_eq!(heap.free_bytes(), total);
+    assert!(heap.allocate(Layout::from_size_align(total, 16).unwrap()).is_some());
+});
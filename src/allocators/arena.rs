@@ -0,0 +1,94 @@
+use core::alloc::{Allocator, Layout, AllocError};
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ArenaStats {
+    pub name: &'static str,
+    pub budget: usize,
+    pub used: usize,
+    pub peak: usize,
+    pub failed: usize,
+}
+
+/// A named allocator that passes allocations on to `backing` for as long as the bytes it has
+/// outstanding stay within its budget.
+pub struct Arena<A: Allocator> {
+    name: &'static str,
+    budget: AtomicUsize,
+    used: AtomicUsize,
+    peak: AtomicUsize,
+    failed: AtomicUsize,
+    backing: A,
+}
+
+impl<A: Allocator> Arena<A> {
+    pub const fn new(name: &'static str, budget: usize, backing: A) -> Self {
+        Self {
+            name,
+            budget: AtomicUsize::new(budget),
+            used: AtomicUsize::new(0),
+            peak: AtomicUsize::new(0),
+            failed: AtomicUsize::new(0),
+            backing,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn set_budget(&self, budget: usize) {
+        self.budget.store(budget, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> ArenaStats {
+        ArenaStats {
+            name: self.name,
+            budget: self.budget.load(Ordering::Relaxed),
+            used: self.used.load(Ordering::Relaxed),
+            peak: self.peak.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+        }
+    }
+
+    fn reserve(&self, size: usize) -> Result<(), AllocError> {
+        let budget = self.budget.load(Ordering::Relaxed);
+        let result = self.used.fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+            used.checked_add(size).filter(|&used| used <= budget)
+        });
+        match result {
+            Ok(used) => {
+                self.peak.fetch_max(used + size, Ordering::Relaxed);
+                Ok(())
+            }
+            Err(_) => {
+                self.failed.fetch_add(1, Ordering::Relaxed);
+                Err(AllocError)
+            }
+        }
+    }
+
+    fn release(&self, size: usize) {
+        self.used.fetch_sub(size, Ordering::AcqRel);
+    }
+}
+
+#[allow(unsafe_code)]
+unsafe impl<A: Allocator> Allocator for Arena<A> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        self.reserve(layout.size())?;
+        let block = self.backing.allocate(layout).inspect_err(|_| {
+            self.release(layout.size());
+            self.failed.fetch_add(1, Ordering::Relaxed);
+        })?;
+        // Only `layout.size()` bytes count against the budget, so don't offer callers the rest of
+        // a larger block: they could hand its full length back to `deallocate`.
+        Ok(NonNull::slice_from_raw_parts(block.cast(), layout.size()))
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.backing.deallocate(ptr, layout);
+        self.release(layout.size());
+    }
+}
@@ -0,0 +1,235 @@
+use core::alloc::Layout;
+use core::mem::size_of;
+use core::ptr::NonNull;
+
+/// A block of free memory, stored in the memory it describes.
+struct FreeBlock {
+    size: usize,
+    next: Option<NonNull<FreeBlock>>,
+}
+
+const MIN_BLOCK_SIZE: usize = size_of::<FreeBlock>();
+// Aligning blocks to their own size keeps every split remainder big enough to hold a `FreeBlock`.
+const BLOCK_ALIGN: usize = MIN_BLOCK_SIZE;
+
+fn align_up(addr: usize, align: usize) -> usize {
+    (addr + align - 1) & !(align - 1)
+}
+
+fn align_down(addr: usize, align: usize) -> usize {
+    addr & !(align - 1)
+}
+
+/// First-fit heap that keeps its free blocks in an address ordered linked list.
+///
+/// Every block handed out is rounded up to a multiple of `MIN_BLOCK_SIZE`, so splitting a free
+/// block never leaves a fragment too small to hold a `FreeBlock`. Freed blocks are merged with
+/// their neighbours, which keeps the list short when allocations are released in any order.
+pub struct FreeListHeap {
+    head: Option<NonNull<FreeBlock>>,
+}
+
+#[allow(unsafe_code)]
+unsafe impl Send for FreeListHeap {}
+
+impl FreeListHeap {
+    pub const fn empty() -> Self {
+        Self { head: None }
+    }
+
+    /// Adds the memory in `start..start + size` to the heap.
+    ///
+    /// # Safety
+    /// The memory must be valid for reads and writes, unused by anything else and must outlive
+    /// the heap.
+    #[allow(unsafe_code)]
+    pub unsafe fn add_region(&mut self, start: usize, size: usize) {
+        let aligned_start = align_up(start, BLOCK_ALIGN);
+        let end = align_down(start + size, BLOCK_ALIGN);
+        if end <= aligned_start || end - aligned_start < MIN_BLOCK_SIZE {
+            return;
+        }
+        self.insert(aligned_start, end - aligned_start);
+    }
+
+    /// The size actually reserved for an allocation of `layout`.
+    pub fn block_size(layout: Layout) -> usize {
+        align_up(layout.size().max(MIN_BLOCK_SIZE), BLOCK_ALIGN)
+    }
+
+    #[allow(unsafe_code)]
+    pub fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        let size = Self::block_size(layout);
+        let align = layout.align().max(BLOCK_ALIGN);
+
+        let mut prev: Option<NonNull<FreeBlock>> = None;
+        let mut current = self.head;
+        while let Some(block_ptr) = current {
+            let block_start = block_ptr.as_ptr() as usize;
+            let (block_size, next) = unsafe {
+                let block = block_ptr.as_ref();
+                (block.size, block.next)
+            };
+            let block_end = block_start + block_size;
+
+            if let Some(alloc_start) = Self::fit(block_start, block_end, size, align) {
+                let alloc_end = alloc_start + size;
+
+                // Unlink the block, then give back whatever is left either side of the allocation.
+                match prev {
+                    Some(mut prev) => unsafe { prev.as_mut().next = next },
+                    None => self.head = next,
+                }
+                unsafe {
+                    if alloc_start > block_start {
+                        self.insert(block_start, alloc_start - block_start);
+                    }
+                    if block_end > alloc_end {
+                        self.insert(alloc_end, block_end - alloc_end);
+                    }
+                }
+                return NonNull::new(alloc_start as *mut u8);
+            }
+
+            prev = current;
+            current = next;
+        }
+        None
+    }
+
+    /// Returns the block at `ptr` to the heap.
+    ///
+    /// # Safety
+    /// `ptr` must have been returned by `allocate` on this heap with the same `layout`.
+    #[allow(unsafe_code)]
+    pub unsafe fn deallocate(&mut self, ptr: NonNull<u8>, layout: Layout) {
+        self.insert(ptr.as_ptr() as usize, Self::block_size(layout));
+    }
+
+    /// Total number of free bytes, including fragments too small for some allocations.
+    #[allow(unsafe_code)]
+    pub fn free_bytes(&self) -> usize {
+        let mut total = 0;
+        let mut current = self.head;
+        while let Some(block) = current {
+            let block = unsafe { block.as_ref() };
+            total += block.size;
+            current = block.next;
+        }
+        total
+    }
+
+    /// Finds where an allocation of `size` bytes aligned to `align` would start within the free
+    /// block `block_start..block_end`, making sure any padding in front of it can be kept as its
+    /// own free block.
+    fn fit(block_start: usize, block_end: usize, size: usize, align: usize) -> Option<usize> {
+        let mut alloc_start = align_up(block_start, align);
+        if alloc_start != block_start && alloc_start - block_start < MIN_BLOCK_SIZE {
+            alloc_start = align_up(block_start + MIN_BLOCK_SIZE, align);
+        }
+        let alloc_end = alloc_start.checked_add(size)?;
+        if alloc_end > block_end {
+            return None;
+        }
+        Some(alloc_start)
+    }
+
+    /// Inserts a free block keeping the list ordered by address, merging it with the blocks
+    /// directly before and after it when they touch.
+    #[allow(unsafe_code)]
+    unsafe fn insert(&mut self, start: usize, size: usize) {
+        let mut prev: Option<NonNull<FreeBlock>> = None;
+        let mut current = self.head;
+        while let Some(block) = current {
+            if block.as_ptr() as usize > start {
+                break;
+            }
+            prev = current;
+            current = block.as_ref().next;
+        }
+
+        let mut block = FreeBlock { size, next: current };
+        if let Some(next) = current {
+            if start + size == next.as_ptr() as usize {
+                let next = next.as_ref();
+                block.size += next.size;
+                block.next = next.next;
+            }
+        }
+
+        match prev {
+            Some(mut prev) if prev.as_ptr() as usize + prev.as_ref().size == start => {
+                let prev = prev.as_mut();
+                prev.size += block.size;
+                prev.next = block.next;
+            }
+            Some(mut prev) => {
+                let ptr = start as *mut FreeBlock;
+                ptr.write(block);
+                prev.as_mut().next = NonNull::new(ptr);
+            }
+            None => {
+                let ptr = start as *mut FreeBlock;
+                ptr.write(block);
+                self.head = NonNull::new(ptr);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn heap_over(memory: &mut [u8]) -> FreeListHeap {
+        let mut heap = FreeListHeap::empty();
+        #[allow(unsafe_code)] unsafe { heap.add_region(memory.as_mut_ptr() as usize, memory.len()) };
+        heap
+    }
+
+    #[test]
+    fn coalesces_blocks_freed_in_any_order() {
+        let mut memory = vec![0u8; 4096];
+        let mut heap = heap_over(&mut memory);
+        let total = heap.free_bytes();
+
+        let layout = Layout::from_size_align(100, 8).unwrap();
+        let blocks: alloc::vec::Vec<_> = (0..8).map(|_| heap.allocate(layout).unwrap()).collect();
+        for &i in [3, 0, 7, 1, 5, 2, 6, 4].iter() {
+            #[allow(unsafe_code)] unsafe { heap.deallocate(blocks[i], layout) };
+        }
+
+        assert_eq!(heap.free_bytes(), total);
+        assert!(heap.allocate(Layout::from_size_align(total, BLOCK_ALIGN).unwrap()).is_some());
+    }
+
+    #[test]
+    fn keeps_alignment_padding_free() {
+        let mut memory = vec![0u8; 8192];
+        let mut heap = heap_over(&mut memory);
+        let total = heap.free_bytes();
+
+        let small = heap.allocate(Layout::from_size_align(16, 16).unwrap()).unwrap();
+        let aligned_layout = Layout::from_size_align(64, 1024).unwrap();
+        let aligned = heap.allocate(aligned_layout).unwrap();
+        assert_eq!(aligned.as_ptr() as usize % 1024, 0);
+        // The padding skipped to reach the alignment is still usable.
+        assert_eq!(heap.free_bytes(), total - MIN_BLOCK_SIZE - 64);
+
+        #[allow(unsafe_code)] unsafe {
+            heap.deallocate(aligned, aligned_layout);
+            heap.deallocate(small, Layout::from_size_align(16, 16).unwrap());
+        }
+        assert_eq!(heap.free_bytes(), total);
+    }
+
+    #[test]
+    fn ignores_regions_too_small_for_a_block() {
+        let mut memory = vec![0u8; 64];
+        let mut heap = FreeListHeap::empty();
+        #[allow(unsafe_code)] unsafe { heap.add_region(memory.as_mut_ptr() as usize + 1, MIN_BLOCK_SIZE) };
+        assert_eq!(heap.free_bytes(), 0);
+        assert!(heap.allocate(Layout::new::<u8>()).is_none());
+    }
+}
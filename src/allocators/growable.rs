@@ -0,0 +1,190 @@
+use super::{FreeListHeap, HeapStats};
+use super::stats::HeapCounters;
+use super::oom;
+#[cfg(feature = "alloc-tracking")]
+use super::tracking;
+use crate::memory::{self, Page, PageFlags, VirtAddr, PAGE_SIZE};
+use core::alloc::{Allocator, GlobalAlloc, Layout, AllocError};
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::ptr::NonNull;
+use spin::Mutex;
+
+/// Minimum number of pages mapped each time the heap grows, so small allocations don't each
+/// take a trip through the page mapper.
+const MIN_GROWTH_PAGES: u64 = 16;
+
+#[repr(align(16))]
+struct Storage<const SIZE: usize>([u8; SIZE]);
+
+struct Growth {
+    start: u64,
+    end: u64,
+    limit: u64,
+}
+
+struct State {
+    heap: Option<FreeListHeap>,
+    growth: Option<Growth>,
+}
+
+/// A heap that starts out in a fixed `SIZE` byte arena and, once `enable_growth` has been
+/// called, maps more frames at the end of its virtual region whenever an allocation doesn't fit.
+pub struct GrowableHeap<const SIZE: usize> {
+    arena: UnsafeCell<MaybeUninit<Storage<SIZE>>>,
+    state: Mutex<State>,
+    counters: HeapCounters,
+}
+
+#[allow(unsafe_code)]
+unsafe impl<const SIZE: usize> Sync for GrowableHeap<SIZE> {}
+
+impl<const SIZE: usize> GrowableHeap<SIZE> {
+    pub const fn new() -> Self {
+        Self {
+            arena: UnsafeCell::new(MaybeUninit::uninit()),
+            state: Mutex::new(State { heap: None, growth: None }),
+            counters: HeapCounters::new(),
+        }
+    }
+
+    /// Lets the heap grow into `start..start + limit`, which must be unmapped and unused.
+    pub fn enable_growth(&self, start: VirtAddr, limit: usize) {
+        let start = start.align_up(PAGE_SIZE).as_u64();
+        let mut lock = self.state.lock();
+        if lock.growth.is_some() {
+            panic!("Heap growth is already enabled");
+        }
+        lock.growth = Some(Growth { start, end: start, limit: limit as u64 });
+    }
+
+    /// Changes how large the growable region may become. Memory that is already mapped stays
+    /// mapped even if it is above the new limit.
+    pub fn set_limit(&self, limit: usize) {
+        if let Some(growth) = &mut self.state.lock().growth {
+            growth.limit = limit as u64;
+        }
+    }
+
+    /// Returns `(mapped, limit)` for the growable region, if growth is enabled.
+    pub fn growth_usage(&self) -> Option<(usize, usize)> {
+        let lock = self.state.lock();
+        let growth = lock.growth.as_ref()?;
+        Some(((growth.end - growth.start) as usize, growth.limit as usize))
+    }
+
+    pub fn stats(&self) -> HeapStats {
+        self.counters.snapshot()
+    }
+
+    fn allocate_inner(&self, layout: Layout) -> Option<NonNull<u8>> {
+        let mut ptr = self.allocate_from_heap(layout);
+        if ptr.is_none() && oom::reclaim(layout) {
+            ptr = self.allocate_from_heap(layout);
+        }
+        match ptr {
+            Some(ptr) => {
+                self.counters.allocated(layout.size());
+                #[cfg(feature = "alloc-tracking")]
+                tracking::record(ptr.as_ptr() as usize, layout, tracking::backtrace());
+                Some(ptr)
+            }
+            None => {
+                self.counters.failed();
+                None
+            }
+        }
+    }
+
+    fn allocate_from_heap(&self, layout: Layout) -> Option<NonNull<u8>> {
+        let mut lock = self.state.lock();
+        let State { heap, growth } = &mut *lock;
+        let heap = heap.get_or_insert_with(|| {
+            let mut heap = FreeListHeap::empty();
+            // The arena lives as long as `self` and is only ever handed out through `heap`.
+            #[allow(unsafe_code)] unsafe { heap.add_region(self.arena.get() as usize, SIZE) };
+            heap
+        });
+
+        if let Some(ptr) = heap.allocate(layout) {
+            return Some(ptr);
+        }
+        let growth = growth.as_mut()?;
+        Self::grow(heap, growth, layout)?;
+        heap.allocate(layout)
+    }
+
+    fn grow(heap: &mut FreeListHeap, growth: &mut Growth, layout: Layout) -> Option<()> {
+        // Enough for the block plus the worst case alignment padding in front of it.
+        let needed = (FreeListHeap::block_size(layout) + layout.align()) as u64;
+        let pages = needed.div_ceil(PAGE_SIZE).max(MIN_GROWTH_PAGES);
+        let available = (growth.start + growth.limit).saturating_sub(growth.end) / PAGE_SIZE;
+        let pages = pages.min(available);
+        if pages * PAGE_SIZE < needed {
+            return None;
+        }
+
+        let first_page = Page::containing_address(VirtAddr::new(growth.end));
+        let flags = PageFlags::WRITABLE | PageFlags::NO_EXECUTE;
+        let mut mapped = 0;
+        while mapped < pages {
+            if memory::map_new_page(first_page.offset(mapped), flags).is_err() {
+                break;
+            }
+            mapped += 1;
+        }
+        if mapped == 0 {
+            return None;
+        }
+
+        let start = growth.end;
+        growth.end += mapped * PAGE_SIZE;
+        // The new pages are mapped, unused and only reachable through the heap.
+        #[allow(unsafe_code)] unsafe { heap.add_region(start as usize, (mapped * PAGE_SIZE) as usize) };
+        Some(())
+    }
+
+    #[allow(unsafe_code)]
+    unsafe fn deallocate_inner(&self, ptr: NonNull<u8>, layout: Layout) {
+        if let Some(heap) = &mut self.state.lock().heap {
+            heap.deallocate(ptr, layout);
+        }
+        self.counters.deallocated(layout.size());
+        #[cfg(feature = "alloc-tracking")]
+        tracking::forget(ptr.as_ptr() as usize);
+    }
+}
+
+impl<const SIZE: usize> Default for GrowableHeap<SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[allow(unsafe_code)]
+unsafe impl<const SIZE: usize> Allocator for GrowableHeap<SIZE> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let ptr = self.allocate_inner(layout).ok_or(AllocError)?;
+        Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.deallocate_inner(ptr, layout);
+    }
+}
+
+#[allow(unsafe_code)]
+unsafe impl<const SIZE: usize> GlobalAlloc for GrowableHeap<SIZE> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        match self.allocate_inner(layout) {
+            Some(ptr) => ptr.as_ptr(),
+            None => core::ptr::null_mut(),
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if let Some(ptr) = NonNull::new(ptr) {
+            self.deallocate_inner(ptr, layout);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+mod arena;
+mod free_list;
+mod growable;
+mod oom;
+mod slab;
+mod stats;
+#[cfg(feature = "alloc-tracking")]
+pub mod tracking;
+#[cfg(test)]
+mod tests;
+
+pub use arena::{Arena, ArenaStats};
+pub use free_list::FreeListHeap;
+pub use growable::GrowableHeap;
+pub use oom::{register_reclaimer, write_report, Reclaimer, TooManyReclaimers};
+pub use slab::{SlabAllocator, CacheStats};
+pub use stats::HeapStats;
+
+use alloc::alloc::Global;
+use crate::memory::VirtAddr;
+
+/// Serves allocations made before the heap is able to grow.
+const BOOTSTRAP_HEAP_SIZE: usize = 64 * 1024;
+
+pub const HEAP_START: u64 = 0x_4444_4444_0000;
+pub const DEFAULT_HEAP_LIMIT: usize = 64 * 1024 * 1024;
+
+// Only the kernel itself runs on this heap, host builds such as tests keep the system allocator.
+#[cfg_attr(target_os = "none", global_allocator)]
+static INTERNAL_ALLOC: InternalAlloc = GrowableHeap::new();
+
+pub(in crate) type InternalAlloc = GrowableHeap<BOOTSTRAP_HEAP_SIZE>;
+
+/// Shared slab caches for small kernel objects, e.g. `Box::new_in(value, &OBJECT_CACHE)`.
+pub static OBJECT_CACHE: SlabAllocator<Global> = SlabAllocator::new_in(Global);
+
+pub type KernelArena = Arena<&'static SlabAllocator<Global>>;
+
+pub static INTERRUPTS_ARENA: KernelArena = Arena::new("interrupts", 64 * 1024, &OBJECT_CACHE);
+pub static DRIVERS_ARENA: KernelArena = Arena::new("drivers", 1024 * 1024, &OBJECT_CACHE);
+pub static EVENT_LOOP_ARENA: KernelArena = Arena::new("event loop", 64 * 1024, &OBJECT_CACHE);
+
+pub fn arenas() -> [&'static KernelArena; 3] {
+    [&INTERRUPTS_ARENA, &DRIVERS_ARENA, &EVENT_LOOP_ARENA]
+}
+
+/// Lets the kernel heap map up to `limit` bytes starting at `HEAP_START` on demand. Needs the
+/// frame allocator and page mapper to be initialised.
+pub fn init_heap(limit: usize) {
+    INTERNAL_ALLOC.enable_growth(VirtAddr::new(HEAP_START), limit);
+}
+
+pub fn set_heap_limit(limit: usize) {
+    INTERNAL_ALLOC.set_limit(limit);
+}
+
+pub fn heap_stats() -> HeapStats {
+    INTERNAL_ALLOC.stats()
+}
+
+/// Returns `(mapped, limit)` for the growable part of the kernel heap, once it can grow.
+pub fn heap_growth_usage() -> Option<(usize, usize)> {
+    INTERNAL_ALLOC.growth_usage()
+}
@@ -0,0 +1,67 @@
+use core::alloc::Layout;
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+use spin::Mutex;
+
+/// Frees what it can to help satisfy `layout`, returning whether anything was freed.
+pub type Reclaimer = fn(Layout) -> bool;
+
+const MAX_RECLAIMERS: usize = 8;
+
+static RECLAIMERS: Mutex<[Option<Reclaimer>; MAX_RECLAIMERS]> = Mutex::new([None; MAX_RECLAIMERS]);
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct TooManyReclaimers;
+
+/// Registers a callback that runs when the kernel heap can't satisfy an allocation, before the
+/// allocation is retried. Reclaimers must not allocate.
+pub fn register_reclaimer(reclaimer: Reclaimer) -> Result<(), TooManyReclaimers> {
+    let mut reclaimers = RECLAIMERS.lock();
+    let slot = reclaimers.iter_mut().find(|slot| slot.is_none()).ok_or(TooManyReclaimers)?;
+    *slot = Some(reclaimer);
+    Ok(())
+}
+
+/// Runs every reclaimer, returning whether any of them freed memory.
+pub(in crate::allocators) fn reclaim(layout: Layout) -> bool {
+    static RECLAIMING: AtomicBool = AtomicBool::new(false);
+    // An allocation failing inside a reclaimer must not start another round.
+    if RECLAIMING.swap(true, Ordering::Acquire) {
+        return false;
+    }
+    let reclaimers = *RECLAIMERS.lock();
+    let mut freed = false;
+    for reclaimer in reclaimers.iter().flatten() {
+        freed |= reclaimer(layout);
+    }
+    RECLAIMING.store(false, Ordering::Release);
+    freed
+}
+
+/// Writes the failed `layout` and the state of the kernel heap to `out`.
+pub fn write_report(out: &mut dyn fmt::Write, layout: Layout) -> fmt::Result {
+    writeln!(out, "Out of memory: failed to allocate {} bytes aligned to {}", layout.size(), layout.align())?;
+    writeln!(out, "heap: {}", super::heap_stats())?;
+    if let Some((mapped, limit)) = super::heap_growth_usage() {
+        writeln!(out, "heap growth: {} of {} bytes mapped", mapped, limit)?;
+    }
+    if let Some((free, total)) = crate::memory::frame_counts() {
+        writeln!(out, "frames: {} of {} free", free, total)?;
+    }
+    for arena in super::arenas().iter() {
+        let stats = arena.stats();
+        writeln!(
+            out,
+            "arena {}: {} of {} bytes used, peak {}, failed {}",
+            stats.name, stats.used, stats.budget, stats.peak, stats.failed,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "none")]
+#[alloc_error_handler]
+fn alloc_error(layout: Layout) -> ! {
+    crate::console::with_console(|console| write_report(console, layout));
+    panic!("Out of memory: failed to allocate {:?}", layout);
+}
@@ -0,0 +1,108 @@
+use core::alloc::{Allocator, Layout, AllocError};
+use core::ptr::NonNull;
+use spin::Mutex;
+
+/// Object sizes served by the slab caches. Anything larger goes straight to the backing allocator.
+const SIZE_CLASSES: [usize; 8] = [16, 32, 64, 128, 256, 512, 1024, 2048];
+const SLAB_SIZE: usize = 4096;
+
+struct FreeObject {
+    next: Option<NonNull<FreeObject>>,
+}
+
+struct Cache {
+    free: Option<NonNull<FreeObject>>,
+    slabs: usize,
+    in_use: usize,
+}
+
+impl Cache {
+    const EMPTY: Self = Self { free: None, slabs: 0, in_use: 0 };
+}
+
+#[allow(unsafe_code)]
+unsafe impl Send for Cache {}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct CacheStats {
+    pub object_size: usize,
+    pub slabs: usize,
+    pub in_use: usize,
+}
+
+/// Serves small fixed-size objects from per-size caches of `SLAB_SIZE` slabs.
+///
+/// Allocating and freeing is a push or pop on the size class's free list. Slabs are taken from
+/// the backing allocator when a cache runs dry and are kept for reuse afterwards.
+pub struct SlabAllocator<A: Allocator> {
+    caches: Mutex<[Cache; SIZE_CLASSES.len()]>,
+    backing: A,
+}
+
+impl<A: Allocator> SlabAllocator<A> {
+    pub const fn new_in(backing: A) -> Self {
+        Self { caches: Mutex::new([Cache::EMPTY; SIZE_CLASSES.len()]), backing }
+    }
+
+    pub fn stats(&self) -> [CacheStats; SIZE_CLASSES.len()] {
+        let caches = self.caches.lock();
+        let mut stats = [CacheStats { object_size: 0, slabs: 0, in_use: 0 }; SIZE_CLASSES.len()];
+        for (i, cache) in caches.iter().enumerate() {
+            stats[i] = CacheStats { object_size: SIZE_CLASSES[i], slabs: cache.slabs, in_use: cache.in_use };
+        }
+        stats
+    }
+
+    /// Objects are aligned to their size, so the class has to cover the alignment as well.
+    fn size_class(layout: Layout) -> Option<usize> {
+        let size = layout.size().max(layout.align());
+        SIZE_CLASSES.iter().position(|&class| class >= size)
+    }
+
+    #[allow(unsafe_code)]
+    fn refill(&self, cache: &mut Cache, object_size: usize) -> Result<(), AllocError> {
+        let layout = Layout::from_size_align(SLAB_SIZE, SLAB_SIZE).map_err(|_| AllocError)?;
+        let slab = self.backing.allocate(layout)?.cast::<u8>();
+        for i in (0..SLAB_SIZE / object_size).rev() {
+            let object = unsafe { slab.as_ptr().add(i * object_size) } as *mut FreeObject;
+            unsafe { object.write(FreeObject { next: cache.free }) };
+            cache.free = NonNull::new(object);
+        }
+        cache.slabs += 1;
+        Ok(())
+    }
+}
+
+#[allow(unsafe_code)]
+unsafe impl<A: Allocator> Allocator for SlabAllocator<A> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let class = match Self::size_class(layout) {
+            Some(class) => class,
+            None => return self.backing.allocate(layout),
+        };
+
+        let mut caches = self.caches.lock();
+        let cache = &mut caches[class];
+        if cache.free.is_none() {
+            self.refill(cache, SIZE_CLASSES[class])?;
+        }
+        let object = cache.free.ok_or(AllocError)?;
+        cache.free = unsafe { object.as_ref().next };
+        cache.in_use += 1;
+        Ok(NonNull::slice_from_raw_parts(object.cast(), SIZE_CLASSES[class]))
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        let class = match Self::size_class(layout) {
+            Some(class) => class,
+            None => return self.backing.deallocate(ptr, layout),
+        };
+
+        let mut caches = self.caches.lock();
+        let cache = &mut caches[class];
+        let object = ptr.cast::<FreeObject>();
+        object.as_ptr().write(FreeObject { next: cache.free });
+        cache.free = Some(object);
+        cache.in_use -= 1;
+    }
+}
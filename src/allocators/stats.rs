@@ -0,0 +1,67 @@
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct HeapStats {
+    pub live_bytes: usize,
+    pub peak_bytes: usize,
+    pub live_allocations: usize,
+    pub total_allocations: usize,
+    pub failed_allocations: usize,
+}
+
+impl fmt::Display for HeapStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "live: {} bytes in {} allocations, peak: {} bytes, total allocations: {}, failed: {}",
+            self.live_bytes, self.live_allocations, self.peak_bytes, self.total_allocations, self.failed_allocations,
+        )
+    }
+}
+
+pub(in crate::allocators) struct HeapCounters {
+    live_bytes: AtomicUsize,
+    peak_bytes: AtomicUsize,
+    live_allocations: AtomicUsize,
+    total_allocations: AtomicUsize,
+    failed_allocations: AtomicUsize,
+}
+
+impl HeapCounters {
+    pub const fn new() -> Self {
+        Self {
+            live_bytes: AtomicUsize::new(0),
+            peak_bytes: AtomicUsize::new(0),
+            live_allocations: AtomicUsize::new(0),
+            total_allocations: AtomicUsize::new(0),
+            failed_allocations: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn allocated(&self, size: usize) {
+        let live = self.live_bytes.fetch_add(size, Ordering::Relaxed) + size;
+        self.peak_bytes.fetch_max(live, Ordering::Relaxed);
+        self.live_allocations.fetch_add(1, Ordering::Relaxed);
+        self.total_allocations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn deallocated(&self, size: usize) {
+        self.live_bytes.fetch_sub(size, Ordering::Relaxed);
+        self.live_allocations.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn failed(&self) {
+        self.failed_allocations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> HeapStats {
+        HeapStats {
+            live_bytes: self.live_bytes.load(Ordering::Relaxed),
+            peak_bytes: self.peak_bytes.load(Ordering::Relaxed),
+            live_allocations: self.live_allocations.load(Ordering::Relaxed),
+            total_allocations: self.total_allocations.load(Ordering::Relaxed),
+            failed_allocations: self.failed_allocations.load(Ordering::Relaxed),
+        }
+    }
+}
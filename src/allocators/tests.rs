@@ -0,0 +1,204 @@
+use super::*;
+use core::alloc::{Allocator, Layout};
+use core::ptr::NonNull;
+use alloc::vec::Vec;
+use alloc::boxed::Box;
+
+/// Small xorshift generator so the randomized tests are reproducible without extra dependencies.
+pub(in crate::allocators) struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed | 1)
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    pub fn below(&mut self, max: usize) -> usize {
+        (self.next() % max as u64) as usize
+    }
+}
+
+struct Live {
+    ptr: NonNull<u8>,
+    layout: Layout,
+    tag: u8,
+}
+
+#[allow(unsafe_code)]
+fn fill(live: &Live) {
+    unsafe { core::ptr::write_bytes(live.ptr.as_ptr(), live.tag, live.layout.size()) };
+}
+
+#[allow(unsafe_code)]
+fn check(live: &Live, len: usize) {
+    let bytes = unsafe { core::slice::from_raw_parts(live.ptr.as_ptr(), len) };
+    assert!(bytes.iter().all(|&byte| byte == live.tag), "allocation at {:p} was overwritten", live.ptr);
+}
+
+fn assert_disjoint(live: &[Live], ptr: NonNull<u8>, layout: Layout) {
+    let start = ptr.as_ptr() as usize;
+    let end = start + layout.size();
+    for other in live {
+        let other_start = other.ptr.as_ptr() as usize;
+        let other_end = other_start + other.layout.size();
+        assert!(end <= other_start || other_end <= start, "{:#x}..{:#x} overlaps {:#x}..{:#x}", start, end, other_start, other_end);
+    }
+}
+
+/// Runs a random mix of allocations, reallocations and frees against `allocator`, checking
+/// alignment, that live allocations never overlap and that their contents survive. Returns how
+/// many allocations succeeded.
+#[allow(unsafe_code)]
+pub(in crate::allocators) fn random_sequence(allocator: &impl Allocator, seed: u64, rounds: usize, max_size: usize) -> usize {
+    let mut rng = Rng::new(seed);
+    let mut live: Vec<Live> = Vec::new();
+    let mut succeeded = 0;
+
+    for round in 0..rounds {
+        match rng.below(4) {
+            0 | 1 => {
+                let layout = Layout::from_size_align(rng.below(max_size), 1 << rng.below(8)).unwrap();
+                if let Ok(ptr) = allocator.allocate(layout) {
+                    let ptr = ptr.cast::<u8>();
+                    assert_eq!(ptr.as_ptr() as usize % layout.align(), 0);
+                    assert_disjoint(&live, ptr, layout);
+                    let entry = Live { ptr, layout, tag: round as u8 };
+                    fill(&entry);
+                    live.push(entry);
+                    succeeded += 1;
+                }
+            }
+            2 if !live.is_empty() => {
+                let entry = live.swap_remove(rng.below(live.len()));
+                check(&entry, entry.layout.size());
+                unsafe { allocator.deallocate(entry.ptr, entry.layout) };
+            }
+            3 if !live.is_empty() => {
+                let index = rng.below(live.len());
+                let old = &live[index];
+                let new_layout = Layout::from_size_align(rng.below(max_size), old.layout.align()).unwrap();
+                let result = unsafe {
+                    if new_layout.size() >= old.layout.size() {
+                        allocator.grow(old.ptr, old.layout, new_layout)
+                    } else {
+                        allocator.shrink(old.ptr, old.layout, new_layout)
+                    }
+                };
+                if let Ok(ptr) = result {
+                    let old = live.swap_remove(index);
+                    let entry = Live { ptr: ptr.cast(), layout: new_layout, tag: old.tag };
+                    check(&entry, old.layout.size().min(new_layout.size()));
+                    assert_disjoint(&live, entry.ptr, new_layout);
+                    fill(&entry);
+                    live.push(entry);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for entry in live {
+        check(&entry, entry.layout.size());
+        unsafe { allocator.deallocate(entry.ptr, entry.layout) };
+    }
+    succeeded
+}
+
+const TEST_HEAP_SIZE: usize = 64 * 1024;
+
+#[test]
+fn growable_heap_reuses_freed_memory() {
+    let allocator = Box::new(GrowableHeap::<TEST_HEAP_SIZE>::new());
+    let layout = Layout::from_size_align(256, 16).unwrap();
+    let mut blocks = Vec::new();
+    while let Ok(ptr) = allocator.allocate(layout) {
+        blocks.push(ptr.cast::<u8>());
+    }
+    assert!(!blocks.is_empty());
+
+    // Free every other block first so coalescing has to join blocks freed out of order.
+    let (even, odd): (Vec<_>, Vec<_>) = blocks.iter().enumerate().partition(|(i, _)| i % 2 == 0);
+    for (_, ptr) in even.into_iter().chain(odd) {
+        #[allow(unsafe_code)] unsafe { allocator.deallocate(*ptr, layout) };
+    }
+
+    let whole = Layout::from_size_align(blocks.len() * 256, 16).unwrap();
+    assert!(allocator.allocate(whole).is_ok());
+}
+
+#[test]
+fn growable_heap_without_growth_survives_random_sequences() {
+    for seed in 0..8 {
+        let heap = Box::new(GrowableHeap::<TEST_HEAP_SIZE>::new());
+        assert!(random_sequence(&*heap, seed, 5_000, 512) > 0);
+
+        let stats = heap.stats();
+        assert_eq!(stats.live_bytes, 0);
+        assert_eq!(stats.live_allocations, 0);
+        assert!(stats.peak_bytes > 0);
+    }
+}
+
+#[test]
+fn growable_heap_counts_failures() {
+    let heap = Box::new(GrowableHeap::<TEST_HEAP_SIZE>::new());
+    let too_big = Layout::from_size_align(TEST_HEAP_SIZE * 2, 16).unwrap();
+    assert!(heap.allocate(too_big).is_err());
+    assert_eq!(heap.stats().failed_allocations, 1);
+}
+
+#[test]
+fn slab_allocator_survives_random_sequences() {
+    let slab = SlabAllocator::new_in(Global);
+    random_sequence(&slab, 7, 10_000, 4096);
+    assert!(slab.stats().iter().all(|cache| cache.in_use == 0));
+}
+
+#[test]
+fn slab_allocator_reuses_objects() {
+    let slab = SlabAllocator::new_in(Global);
+    let layout = Layout::new::<[u64; 4]>();
+    let first = slab.allocate(layout).unwrap().cast::<u8>();
+    #[allow(unsafe_code)] unsafe { slab.deallocate(first, layout) };
+    let second = slab.allocate(layout).unwrap().cast::<u8>();
+    assert_eq!(first, second);
+    #[allow(unsafe_code)] unsafe { slab.deallocate(second, layout) };
+}
+
+#[test]
+fn arena_enforces_its_budget() {
+    let arena = Arena::new("test", 1024, Global);
+    let layout = Layout::from_size_align(600, 8).unwrap();
+    let first = arena.allocate(layout).unwrap();
+    assert!(arena.allocate(layout).is_err());
+
+    let stats = arena.stats();
+    assert_eq!(stats.used, 600);
+    assert_eq!(stats.failed, 1);
+
+    #[allow(unsafe_code)] unsafe { arena.deallocate(first.cast(), layout) };
+    let stats = arena.stats();
+    assert_eq!(stats.used, 0);
+    assert_eq!(stats.peak, 600);
+    assert!(arena.allocate(layout).is_ok());
+}
+
+#[test]
+fn arena_accounts_for_the_size_it_returned() {
+    let slab = SlabAllocator::new_in(Global);
+    let arena = Arena::new("test", 1024, &slab);
+    let layout = Layout::from_size_align(100, 8).unwrap();
+    let block = arena.allocate(layout).unwrap();
+    assert_eq!(block.len(), 100);
+
+    // Freeing with the returned length is allowed and must give back exactly what was reserved.
+    let returned = Layout::from_size_align(block.len(), 8).unwrap();
+    #[allow(unsafe_code)] unsafe { arena.deallocate(block.cast(), returned) };
+    assert_eq!(arena.stats().used, 0);
+}
@@ -0,0 +1,103 @@
+use core::alloc::Layout;
+use core::fmt;
+use spin::Mutex;
+
+/// Return addresses recorded for each allocation, innermost first.
+pub const BACKTRACE_DEPTH: usize = 6;
+const MAX_TRACKED: usize = 1024;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct AllocationRecord {
+    pub address: usize,
+    pub layout: Layout,
+    pub callers: [usize; BACKTRACE_DEPTH],
+}
+
+struct Records {
+    entries: [Option<AllocationRecord>; MAX_TRACKED],
+    untracked: usize,
+}
+
+static RECORDS: Mutex<Records> = Mutex::new(Records { entries: [None; MAX_TRACKED], untracked: 0 });
+
+/// Walks the frame pointer chain of the current call stack. Needs the kernel to be built with
+/// frame pointers, otherwise the addresses are meaningless.
+#[cfg(target_arch = "x86_64")]
+#[inline(always)]
+#[allow(unsafe_code)]
+pub(in crate::allocators) fn backtrace() -> [usize; BACKTRACE_DEPTH] {
+    let mut callers = [0; BACKTRACE_DEPTH];
+    let mut frame: usize;
+    unsafe { core::arch::asm!("mov {}, rbp", out(reg) frame, options(nomem, nostack, preserves_flags)) };
+    for caller in callers.iter_mut() {
+        if frame == 0 || !frame.is_multiple_of(core::mem::align_of::<usize>()) {
+            break;
+        }
+        let (next, return_address) = unsafe {
+            let frame = frame as *const usize;
+            (*frame, *frame.add(1))
+        };
+        *caller = return_address;
+        // Stacks grow down, so anything that isn't further up the stack is a broken chain.
+        if next <= frame {
+            break;
+        }
+        frame = next;
+    }
+    callers
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+pub(in crate::allocators) fn backtrace() -> [usize; BACKTRACE_DEPTH] {
+    [0; BACKTRACE_DEPTH]
+}
+
+pub(in crate::allocators) fn record(address: usize, layout: Layout, callers: [usize; BACKTRACE_DEPTH]) {
+    let mut records = RECORDS.lock();
+    match records.entries.iter_mut().find(|entry| entry.is_none()) {
+        Some(entry) => *entry = Some(AllocationRecord { address, layout, callers }),
+        None => records.untracked += 1,
+    }
+}
+
+pub(in crate::allocators) fn forget(address: usize) {
+    let mut records = RECORDS.lock();
+    let entry = records.entries.iter_mut()
+        .find(|entry| matches!(entry, Some(record) if record.address == address));
+    match entry {
+        Some(entry) => *entry = None,
+        None => records.untracked = records.untracked.saturating_sub(1),
+    }
+}
+
+/// Calls `f` with every outstanding allocation of the kernel heap.
+pub fn for_each_outstanding(f: impl FnMut(&AllocationRecord)) {
+    let records = RECORDS.lock();
+    records.entries.iter().flatten().for_each(f);
+}
+
+/// Number of outstanding allocations that didn't fit in the tracking table.
+pub fn untracked() -> usize {
+    RECORDS.lock().untracked
+}
+
+/// Writes every outstanding allocation to `out`, one per line.
+pub fn dump(out: &mut dyn fmt::Write) -> fmt::Result {
+    let records = RECORDS.lock();
+    for record in records.entries.iter().flatten() {
+        write!(out, "{:#x}: {} bytes, align {}, callers:", record.address, record.layout.size(), record.layout.align())?;
+        for caller in record.callers.iter().take_while(|&&caller| caller != 0) {
+            write!(out, " {:#x}", caller)?;
+        }
+        writeln!(out)?;
+    }
+    if records.untracked != 0 {
+        writeln!(out, "{} allocations were not tracked", records.untracked)?;
+    }
+    Ok(())
+}
+
+/// Writes every outstanding allocation to the kernel console.
+pub fn dump_to_console() {
+    crate::console::with_console(|console| dump(console));
+}
@@ -0,0 +1,34 @@
+use core::fmt::{self, Write};
+use alloc::boxed::Box;
+use spin::Mutex;
+
+static CONSOLE: Mutex<Option<Box<dyn Write + Send>>> = Mutex::new(None);
+
+/// Sets where `print!` and kernel diagnostics are written, e.g. a serial port or terminal.
+pub fn set_console(console: Box<dyn Write + Send>) {
+    *CONSOLE.lock() = Some(console);
+}
+
+/// Runs `f` with the console, if one is set. Gives up instead of spinning when the console is
+/// already in use, so it is safe to call from paths that may have interrupted a writer.
+pub fn with_console<R>(f: impl FnOnce(&mut dyn Write) -> R) -> Option<R> {
+    let mut lock = CONSOLE.try_lock()?;
+    let console = lock.as_mut()?;
+    Some(f(&mut **console))
+}
+
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments) {
+    with_console(|console| console.write_fmt(args));
+}
+
+#[macro_export]
+macro_rules! print {
+    ($($arg:tt)*) => ($crate::console::_print(format_args!($($arg)*)));
+}
+
+#[macro_export]
+macro_rules! println {
+    () => ($crate::print!("\n"));
+    ($($arg:tt)*) => ($crate::print!("{}\n", format_args!($($arg)*)));
+}
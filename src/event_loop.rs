@@ -0,0 +1,348 @@
+use crate::sync::Ring;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use spin::Mutex;
+
+/// How many event types can be registered with `register_event_type`.
+pub const MAX_CUSTOM_EVENT_TYPES: usize = 64;
+
+static CUSTOM_EVENT_TYPES: Mutex<[Option<&'static str>; MAX_CUSTOM_EVENT_TYPES]> =
+    Mutex::new([None; MAX_CUSTOM_EVENT_TYPES]);
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Event {
+    Keyboard(u8),
+    Timer,
+    DeviceAdded(u32),
+    DeviceRemoved(u32),
+    /// An event type a driver registered for itself, with whatever data it wants to pass along.
+    Custom(CustomEventId, usize),
+}
+
+impl Event {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Event::Keyboard(_) => EventKind::Keyboard,
+            Event::Timer => EventKind::Timer,
+            Event::DeviceAdded(_) | Event::DeviceRemoved(_) => EventKind::Device,
+            Event::Custom(id, _) => EventKind::Custom(*id),
+        }
+    }
+}
+
+/// What handlers can subscribe to. Device hotplug events share a kind, whether the device came
+/// or went.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum EventKind {
+    Keyboard,
+    Timer,
+    Device,
+    Custom(CustomEventId),
+}
+
+/// An event type registered with `register_event_type`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct CustomEventId(u16);
+
+impl CustomEventId {
+    pub fn name(self) -> &'static str {
+        CUSTOM_EVENT_TYPES.lock()[usize::from(self.0)].unwrap_or("unknown")
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct TooManyEventTypes;
+
+/// Gets an ID for events that don't have a variant of their own. Registering a name that is
+/// already registered returns the same ID, so a publisher and its subscribers don't need to share
+/// anything but the name.
+pub fn register_event_type(name: &'static str) -> Result<CustomEventId, TooManyEventTypes> {
+    let mut types = CUSTOM_EVENT_TYPES.lock();
+    let index = types.iter().position(|registered| *registered == Some(name))
+        .or_else(|| {
+            let index = types.iter().position(Option::is_none)?;
+            types[index] = Some(name);
+            Some(index)
+        })
+        .ok_or(TooManyEventTypes)?;
+    Ok(CustomEventId(index as u16))
+}
+
+pub type EventHandler = fn(Event);
+
+/// Which events a handler gets: those of one kind, or all of them, optionally narrowed down
+/// further by a predicate.
+#[derive(Debug, Copy, Clone)]
+pub struct EventFilter {
+    kind: Option<EventKind>,
+    predicate: Option<fn(&Event) -> bool>,
+}
+
+impl EventFilter {
+    pub const ALL: Self = Self { kind: None, predicate: None };
+
+    pub const fn kind(kind: EventKind) -> Self {
+        Self { kind: Some(kind), predicate: None }
+    }
+
+    /// Only lets through the events `predicate` returns `true` for, on top of any kind.
+    pub const fn matching(self, predicate: fn(&Event) -> bool) -> Self {
+        Self { predicate: Some(predicate), ..self }
+    }
+
+    pub fn matches(&self, event: &Event) -> bool {
+        self.kind.is_none_or(|kind| event.kind() == kind)
+            && self.predicate.is_none_or(|predicate| predicate(event))
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Subscription {
+    filter: EventFilter,
+    handler: EventHandler,
+}
+
+type NextTickCallback = fn();
+
+/// Which slot a handler was registered in, for `EventLoop::remove_handler`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct EventHandlerId(usize);
+
+/// The event was dropped because the queue was full. The event loop also counts these, see
+/// `EventLoop::dropped_events`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct EventQueueFull(pub Event);
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct TooManyHandlers;
+
+/// Queues events and hands them to every registered handler when polled, without allocating.
+///
+/// Everything takes a shared reference, so an event loop can live in a plain `static`. Events go
+/// through a lock-free ring, so `emit_event` can be called from any interrupt handler, while the
+/// handler lists are behind locks and must only be touched from normal context. When the queue is
+/// full the new event is dropped, never an older one. `QUEUE_CAP` must be a power of two.
+pub struct EventLoop<const QUEUE_CAP: usize, const HANDLER_CAP: usize, const NEXT_TICK_HANDLER_CAP: usize> {
+    queue: Ring<Event, QUEUE_CAP>,
+    handlers: Mutex<[Option<Subscription>; HANDLER_CAP]>,
+    next_tick: Mutex<[Option<NextTickCallback>; NEXT_TICK_HANDLER_CAP]>,
+    dropped: AtomicUsize,
+    polling: AtomicBool,
+}
+
+impl<const QUEUE_CAP: usize, const HANDLER_CAP: usize, const NEXT_TICK_HANDLER_CAP: usize>
+    EventLoop<QUEUE_CAP, HANDLER_CAP, NEXT_TICK_HANDLER_CAP>
+{
+    pub const fn new() -> Self {
+        Self {
+            queue: Ring::new(),
+            handlers: Mutex::new([None; HANDLER_CAP]),
+            next_tick: Mutex::new([None; NEXT_TICK_HANDLER_CAP]),
+            dropped: AtomicUsize::new(0),
+            polling: AtomicBool::new(false),
+        }
+    }
+
+    /// Hands every event to `handler`.
+    pub fn add_handler(&self, handler: EventHandler) -> Result<EventHandlerId, TooManyHandlers> {
+        self.subscribe(EventFilter::ALL, handler)
+    }
+
+    /// Hands the events `filter` matches to `handler`.
+    pub fn subscribe(&self, filter: EventFilter, handler: EventHandler) -> Result<EventHandlerId, TooManyHandlers> {
+        let mut handlers = self.handlers.lock();
+        let index = handlers.iter().position(Option::is_none).ok_or(TooManyHandlers)?;
+        handlers[index] = Some(Subscription { filter, handler });
+        Ok(EventHandlerId(index))
+    }
+
+    pub fn remove_handler(&self, id: EventHandlerId) -> Option<EventHandler> {
+        self.handlers.lock()[id.0].take().map(|subscription| subscription.handler)
+    }
+
+    /// Runs `callback` at the start of the next `poll`, before any events are handled.
+    pub fn next_tick(&self, callback: NextTickCallback) -> Result<(), TooManyHandlers> {
+        let mut next_tick = self.next_tick.lock();
+        let slot = next_tick.iter_mut().find(|slot| slot.is_none()).ok_or(TooManyHandlers)?;
+        *slot = Some(callback);
+        Ok(())
+    }
+
+    /// Queues `event` for the next `poll`. Safe to call from interrupt context.
+    pub fn emit_event(&self, event: Event) -> Result<(), EventQueueFull> {
+        self.queue.push(event).map_err(|event| {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            EventQueueFull(event)
+        })
+    }
+
+    /// Runs the next tick callbacks, then hands every queued event to each handler subscribed to
+    /// it, in the order they were registered. Returns how many events were handled.
+    ///
+    /// Only one poll runs at a time; a poll that starts while another is running, such as one
+    /// from inside a handler, returns 0 straight away.
+    pub fn poll(&self) -> usize {
+        if self.polling.swap(true, Ordering::Acquire) {
+            return 0;
+        }
+
+        // The locks aren't held while callbacks and handlers run, so they can register more.
+        // Callbacks registered while these run wait for the next poll.
+        let next_tick = core::mem::replace(&mut *self.next_tick.lock(), [None; NEXT_TICK_HANDLER_CAP]);
+        next_tick.iter().flatten().for_each(|callback| callback());
+
+        let mut handled = 0;
+        // Handle at most a queue's worth of events, so a steady stream of events can't keep it
+        // from returning. Events emitted during the poll may be handled by it.
+        for _ in 0..QUEUE_CAP {
+            let event = match self.queue.pop() {
+                Some(event) => event,
+                None => break,
+            };
+            let handlers = *self.handlers.lock();
+            handlers.iter().flatten()
+                .filter(|subscription| subscription.filter.matches(&event))
+                .for_each(|subscription| (subscription.handler)(event));
+            handled += 1;
+        }
+
+        self.polling.store(false, Ordering::Release);
+        handled
+    }
+
+    /// How many events `emit_event` has dropped because the queue was full.
+    pub fn dropped_events(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl<const QUEUE_CAP: usize, const HANDLER_CAP: usize, const NEXT_TICK_HANDLER_CAP: usize> Default
+    for EventLoop<QUEUE_CAP, HANDLER_CAP, NEXT_TICK_HANDLER_CAP>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spin::Mutex;
+    use alloc::vec::Vec;
+    use std::thread;
+
+    #[test]
+    fn hands_events_to_every_handler_after_next_tick_callbacks() {
+        static SEEN: Mutex<Vec<&str>> = Mutex::new(Vec::new());
+        fn first(event: Event) {
+            if let Event::Keyboard(_) = event {
+                SEEN.lock().push("first");
+            }
+        }
+        fn second(_: Event) {
+            SEEN.lock().push("second");
+        }
+        fn tick() {
+            SEEN.lock().push("tick");
+        }
+
+        let event_loop = EventLoop::<4, 2, 1>::new();
+        event_loop.add_handler(first).unwrap();
+        let id = event_loop.add_handler(second).unwrap();
+        assert_eq!(event_loop.add_handler(second), Err(TooManyHandlers));
+        event_loop.next_tick(tick).unwrap();
+
+        event_loop.emit_event(Event::Keyboard(0x1e)).unwrap();
+        event_loop.emit_event(Event::Timer).unwrap();
+        assert_eq!(event_loop.poll(), 2);
+        assert_eq!(*SEEN.lock(), ["tick", "first", "second", "second"]);
+
+        event_loop.remove_handler(id);
+        event_loop.emit_event(Event::Timer).unwrap();
+        assert_eq!(event_loop.poll(), 1);
+        assert_eq!(SEEN.lock().len(), 4);
+    }
+
+    #[test]
+    fn only_hands_handlers_the_events_they_subscribed_to() {
+        static SEEN: Mutex<Vec<(&str, Event)>> = Mutex::new(Vec::new());
+        fn keys(event: Event) {
+            SEEN.lock().push(("keys", event));
+        }
+        fn releases(event: Event) {
+            SEEN.lock().push(("releases", event));
+        }
+        fn custom(event: Event) {
+            SEEN.lock().push(("custom", event));
+        }
+        fn is_release(event: &Event) -> bool {
+            matches!(event, Event::Keyboard(scancode) if scancode & 0x80 != 0)
+        }
+
+        let blink = register_event_type("cursor blink").unwrap();
+        let event_loop = EventLoop::<8, 3, 1>::new();
+        event_loop.subscribe(EventFilter::kind(EventKind::Keyboard), keys).unwrap();
+        event_loop.subscribe(EventFilter::kind(EventKind::Keyboard).matching(is_release), releases).unwrap();
+        event_loop.subscribe(EventFilter::kind(EventKind::Custom(blink)), custom).unwrap();
+
+        for event in [Event::Keyboard(0x1e), Event::Timer, Event::Keyboard(0x9e), Event::DeviceAdded(1), Event::Custom(blink, 7)] {
+            event_loop.emit_event(event).unwrap();
+        }
+        assert_eq!(event_loop.poll(), 5);
+        assert_eq!(*SEEN.lock(), [
+            ("keys", Event::Keyboard(0x1e)),
+            ("keys", Event::Keyboard(0x9e)),
+            ("releases", Event::Keyboard(0x9e)),
+            ("custom", Event::Custom(blink, 7)),
+        ]);
+    }
+
+    #[test]
+    fn registering_an_event_type_twice_returns_the_same_id() {
+        let hotkey = register_event_type("hotkey").unwrap();
+        let mouse = register_event_type("mouse moved").unwrap();
+        assert_ne!(hotkey, mouse);
+        assert_eq!(register_event_type("hotkey"), Ok(hotkey));
+        assert_eq!(mouse.name(), "mouse moved");
+        assert_eq!(Event::Custom(mouse, 0).kind(), EventKind::Custom(mouse));
+    }
+
+    #[test]
+    fn drops_and_counts_events_when_the_queue_is_full() {
+        let event_loop = EventLoop::<2, 1, 1>::new();
+        event_loop.emit_event(Event::Keyboard(1)).unwrap();
+        event_loop.emit_event(Event::Keyboard(2)).unwrap();
+        assert_eq!(event_loop.emit_event(Event::Keyboard(3)), Err(EventQueueFull(Event::Keyboard(3))));
+        assert_eq!(event_loop.dropped_events(), 1);
+        assert_eq!(event_loop.poll(), 2);
+    }
+
+    #[test]
+    fn a_static_event_loop_takes_events_from_many_threads() {
+        static EVENT_LOOP: EventLoop<64, 1, 1> = EventLoop::new();
+        static KEYS: AtomicUsize = AtomicUsize::new(0);
+        fn count(event: Event) {
+            if let Event::Keyboard(key) = event {
+                KEYS.fetch_add(usize::from(key), Ordering::Relaxed);
+            }
+        }
+        EVENT_LOOP.add_handler(count).unwrap();
+
+        let producers: Vec<_> = (1..=4u8)
+            .map(|key| thread::spawn(move || {
+                for _ in 0..10 {
+                    while EVENT_LOOP.emit_event(Event::Keyboard(key)).is_err() {
+                        thread::yield_now();
+                    }
+                }
+            }))
+            .collect();
+        let mut handled = 0;
+        while handled < 40 {
+            handled += EVENT_LOOP.poll();
+        }
+        producers.into_iter().for_each(|producer| producer.join().unwrap());
+
+        assert_eq!(handled, 40);
+        assert_eq!(KEYS.load(Ordering::Relaxed), 10 * (1 + 2 + 3 + 4));
+    }
+}
@@ -0,0 +1,63 @@
+use crate::sync::Ring;
+
+/// How many work items can be waiting at once.
+pub const DEFERRED_QUEUE_SIZE: usize = 256;
+
+static DEFERRED: Ring<DeferredWork, DEFERRED_QUEUE_SIZE> = Ring::new();
+
+/// A function to call later along with its argument. Work items are plain data so interrupt
+/// handlers can queue them without allocating.
+#[derive(Debug, Copy, Clone)]
+pub struct DeferredWork {
+    pub run: fn(usize),
+    pub data: usize,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct DeferredQueueFull;
+
+/// Queues `run(data)` to be called by `run_deferred`. Safe to call from interrupt context.
+pub fn defer(run: fn(usize), data: usize) -> Result<(), DeferredQueueFull> {
+    DEFERRED.push(DeferredWork { run, data }).map_err(|_| DeferredQueueFull)
+}
+
+/// Runs queued work until the queue is empty, returning how many items ran. Meant to be called
+/// outside interrupt context with interrupts enabled, e.g. from the idle loop.
+pub fn run_deferred() -> usize {
+    let mut count = 0;
+    while let Some(work) = DEFERRED.pop() {
+        (work.run)(work.data);
+        count += 1;
+    }
+    count
+}
+
+pub fn has_deferred_work() -> bool {
+    !DEFERRED.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    static SUM: AtomicUsize = AtomicUsize::new(0);
+
+    fn add(value: usize) {
+        SUM.fetch_add(value, Ordering::Relaxed);
+        // Work queued while running is picked up by the same call.
+        if value == 1 {
+            defer(add, 100).unwrap();
+        }
+    }
+
+    #[test]
+    fn runs_work_queued_before_and_during_the_call() {
+        defer(add, 1).unwrap();
+        defer(add, 10).unwrap();
+        assert!(has_deferred_work());
+        assert_eq!(run_deferred(), 3);
+        assert_eq!(SUM.load(Ordering::Relaxed), 111);
+        assert!(!has_deferred_work());
+    }
+}
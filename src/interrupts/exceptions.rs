@@ -0,0 +1,219 @@
+use super::handler_list::HandlerList;
+use core::sync::atomic::{AtomicU64, Ordering};
+use alloc::boxed::Box;
+use crate::allocators::KernelArena;
+
+const EXCEPTION_KINDS: usize = ExceptionKind::Security as usize + 1;
+
+static EXCEPTION_HANDLERS: [HandlerList<ExceptionHandler>; EXCEPTION_KINDS] =
+    [const { HandlerList::new() }; EXCEPTION_KINDS];
+
+/// Handlers are allocated in `allocators::INTERRUPTS_ARENA`, like interrupt handlers.
+pub type ExceptionHandler = Box<dyn FnMut(&ExceptionContext) -> ExceptionAction + Send, &'static KernelArena>;
+
+/// An architectural exception along with the error code the CPU pushed for it, if any.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Exception {
+    DivideByZero,
+    Debug,
+    NonMaskableInterrupt,
+    Breakpoint,
+    Overflow,
+    BoundRangeExceeded,
+    InvalidOpcode,
+    DeviceNotAvailable,
+    DoubleFault,
+    InvalidTss { error_code: u64 },
+    SegmentNotPresent { error_code: u64 },
+    StackSegmentFault { error_code: u64 },
+    GeneralProtectionFault { error_code: u64 },
+    PageFault { address: u64, error_code: u64 },
+    X87FloatingPoint,
+    AlignmentCheck { error_code: u64 },
+    MachineCheck,
+    SimdFloatingPoint,
+    Virtualization,
+    ControlProtection { error_code: u64 },
+    HypervisorInjection,
+    VmmCommunication { error_code: u64 },
+    Security { error_code: u64 },
+}
+
+/// Which exception occurred, without its details. Handlers are registered per kind.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum ExceptionKind {
+    DivideByZero = 0,
+    Debug,
+    NonMaskableInterrupt,
+    Breakpoint,
+    Overflow,
+    BoundRangeExceeded,
+    InvalidOpcode,
+    DeviceNotAvailable,
+    DoubleFault,
+    InvalidTss,
+    SegmentNotPresent,
+    StackSegmentFault,
+    GeneralProtectionFault,
+    PageFault,
+    X87FloatingPoint,
+    AlignmentCheck,
+    MachineCheck,
+    SimdFloatingPoint,
+    Virtualization,
+    ControlProtection,
+    HypervisorInjection,
+    VmmCommunication,
+    Security,
+}
+
+impl Exception {
+    pub fn kind(&self) -> ExceptionKind {
+        match self {
+            Self::DivideByZero => ExceptionKind::DivideByZero,
+            Self::Debug => ExceptionKind::Debug,
+            Self::NonMaskableInterrupt => ExceptionKind::NonMaskableInterrupt,
+            Self::Breakpoint => ExceptionKind::Breakpoint,
+            Self::Overflow => ExceptionKind::Overflow,
+            Self::BoundRangeExceeded => ExceptionKind::BoundRangeExceeded,
+            Self::InvalidOpcode => ExceptionKind::InvalidOpcode,
+            Self::DeviceNotAvailable => ExceptionKind::DeviceNotAvailable,
+            Self::DoubleFault => ExceptionKind::DoubleFault,
+            Self::InvalidTss { .. } => ExceptionKind::InvalidTss,
+            Self::SegmentNotPresent { .. } => ExceptionKind::SegmentNotPresent,
+            Self::StackSegmentFault { .. } => ExceptionKind::StackSegmentFault,
+            Self::GeneralProtectionFault { .. } => ExceptionKind::GeneralProtectionFault,
+            Self::PageFault { .. } => ExceptionKind::PageFault,
+            Self::X87FloatingPoint => ExceptionKind::X87FloatingPoint,
+            Self::AlignmentCheck { .. } => ExceptionKind::AlignmentCheck,
+            Self::MachineCheck => ExceptionKind::MachineCheck,
+            Self::SimdFloatingPoint => ExceptionKind::SimdFloatingPoint,
+            Self::Virtualization => ExceptionKind::Virtualization,
+            Self::ControlProtection { .. } => ExceptionKind::ControlProtection,
+            Self::HypervisorInjection => ExceptionKind::HypervisorInjection,
+            Self::VmmCommunication { .. } => ExceptionKind::VmmCommunication,
+            Self::Security { .. } => ExceptionKind::Security,
+        }
+    }
+
+    pub fn error_code(&self) -> Option<u64> {
+        match *self {
+            Self::InvalidTss { error_code }
+            | Self::SegmentNotPresent { error_code }
+            | Self::StackSegmentFault { error_code }
+            | Self::GeneralProtectionFault { error_code }
+            | Self::PageFault { error_code, .. }
+            | Self::AlignmentCheck { error_code }
+            | Self::ControlProtection { error_code }
+            | Self::VmmCommunication { error_code }
+            | Self::Security { error_code } => Some(error_code),
+            _ => None,
+        }
+    }
+}
+
+/// Where an exception happened. For a page fault `faulting_address` is the address that was
+/// accessed, for everything else it is the instruction that raised the exception.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ExceptionContext {
+    pub exception: Exception,
+    pub instruction_pointer: u64,
+    pub stack_pointer: u64,
+}
+
+impl ExceptionContext {
+    pub fn faulting_address(&self) -> u64 {
+        match self.exception {
+            Exception::PageFault { address, .. } => address,
+            _ => self.instruction_pointer,
+        }
+    }
+}
+
+/// What a handler wants done about an exception.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
+pub enum ExceptionAction {
+    /// Not handled, ask the next handler.
+    Continue,
+    /// The cause was fixed, return to the faulting code.
+    Resume,
+    /// Stop the task that caused the exception but keep the kernel running.
+    KillTask,
+    Panic,
+}
+
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq)]
+pub struct ExceptionHandlerId(u64, ExceptionKind);
+
+impl ExceptionHandlerId {
+    fn new(kind: ExceptionKind) -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT_ID.fetch_add(1, Ordering::Relaxed), kind)
+    }
+}
+
+/// Not for use from exception or interrupt handlers, see `set_handler`.
+pub fn set_exception_handler(kind: ExceptionKind, handler: ExceptionHandler) -> ExceptionHandlerId {
+    let id = ExceptionHandlerId::new(kind);
+    EXCEPTION_HANDLERS[kind as usize].insert(id.0, 0, handler);
+    id
+}
+
+/// Removes a handler. Returns `None` if the handler is running, in which case it is dropped once
+/// it finishes. Like `set_exception_handler`, must not be called from interrupt context.
+pub fn remove_exception_handler(id: ExceptionHandlerId) -> Option<ExceptionHandler> {
+    EXCEPTION_HANDLERS[id.1 as usize].remove(id.0)
+}
+
+/// Asks the registered handlers what to do about an exception, in registration order. Returns
+/// `None` when no handler decides, leaving the default up to the caller. Never returns
+/// `Continue`.
+pub fn emit_exception(context: &ExceptionContext) -> Option<ExceptionAction> {
+    EXCEPTION_HANDLERS[context.exception.kind() as usize]
+        .dispatch(|handler| match handler(context) {
+            ExceptionAction::Continue => None,
+            action => Some(action),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::allocators::INTERRUPTS_ARENA;
+
+    fn context(exception: Exception) -> ExceptionContext {
+        ExceptionContext { exception, instruction_pointer: 0x1000, stack_pointer: 0x2000 }
+    }
+
+    #[test]
+    fn undecided_without_a_handler() {
+        assert_eq!(emit_exception(&context(Exception::Virtualization)), None);
+    }
+
+    #[test]
+    fn first_handler_that_decides_wins() {
+        let skip = set_exception_handler(ExceptionKind::Overflow, Box::new_in(|_: &ExceptionContext| ExceptionAction::Continue, &INTERRUPTS_ARENA));
+        let resume = set_exception_handler(ExceptionKind::Overflow, Box::new_in(|_: &ExceptionContext| ExceptionAction::Resume, &INTERRUPTS_ARENA));
+        let kill = set_exception_handler(ExceptionKind::Overflow, Box::new_in(|_: &ExceptionContext| ExceptionAction::KillTask, &INTERRUPTS_ARENA));
+        assert_eq!(emit_exception(&context(Exception::Overflow)), Some(ExceptionAction::Resume));
+
+        assert!(remove_exception_handler(resume).is_some());
+        assert_eq!(emit_exception(&context(Exception::Overflow)), Some(ExceptionAction::KillTask));
+
+        assert!(remove_exception_handler(kill).is_some());
+        let panic = set_exception_handler(ExceptionKind::Overflow, Box::new_in(|_: &ExceptionContext| ExceptionAction::Panic, &INTERRUPTS_ARENA));
+        assert_eq!(emit_exception(&context(Exception::Overflow)), Some(ExceptionAction::Panic));
+
+        assert!(remove_exception_handler(skip).is_some());
+        assert!(remove_exception_handler(panic).is_some());
+        assert_eq!(emit_exception(&context(Exception::Overflow)), None);
+    }
+
+    #[test]
+    fn page_faults_report_the_accessed_address() {
+        let context = context(Exception::PageFault { address: 0xdead_0000, error_code: 2 });
+        assert_eq!(context.faulting_address(), 0xdead_0000);
+        assert_eq!(context.exception.error_code(), Some(2));
+        assert_eq!(context.exception.kind(), ExceptionKind::PageFault);
+    }
+}
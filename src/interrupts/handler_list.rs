@@ -0,0 +1,291 @@
+use crate::allocators::{KernelArena, INTERRUPTS_ARENA};
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use spin::Mutex;
+
+type Snapshot<H> = Vec<Arc<Entry<H>, &'static KernelArena>, &'static KernelArena>;
+
+/// How many dispatches are running on this CPU, so changes from inside a handler can be caught.
+/// The kernel runs on a single CPU, while host tests use threads in place of CPUs.
+#[cfg(not(test))]
+static DISPATCH_DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(test)]
+std::thread_local! {
+    static DISPATCH_DEPTH: AtomicUsize = const { AtomicUsize::new(0) };
+}
+
+fn with_dispatch_depth<R>(f: impl FnOnce(&AtomicUsize) -> R) -> R {
+    #[cfg(not(test))]
+    return f(&DISPATCH_DEPTH);
+    #[cfg(test)]
+    return DISPATCH_DEPTH.with(f);
+}
+
+/// Counts a dispatch as running on this CPU until dropped.
+struct Dispatching;
+
+impl Dispatching {
+    fn enter() -> Self {
+        with_dispatch_depth(|depth| depth.fetch_add(1, Ordering::Relaxed));
+        Self
+    }
+
+    fn is_running() -> bool {
+        with_dispatch_depth(|depth| depth.load(Ordering::Relaxed)) != 0
+    }
+}
+
+impl Drop for Dispatching {
+    fn drop(&mut self) {
+        with_dispatch_depth(|depth| depth.fetch_sub(1, Ordering::Relaxed));
+    }
+}
+
+struct Entry<H> {
+    id: u64,
+    priority: u8,
+    handler: Mutex<Option<H>>,
+}
+
+/// Snapshots that have been replaced but may still be in use by a dispatch.
+struct Retired<H>(Vec<*mut Snapshot<H>>);
+
+#[allow(unsafe_code)]
+unsafe impl<H: Send> Send for Retired<H> {}
+
+/// A list of handlers that can be walked from interrupt context while other code changes it.
+///
+/// Dispatching never takes a lock that non-interrupt code can hold: it reads the current
+/// snapshot of the list, which writers replace with an updated copy instead of changing it in
+/// place. Replaced snapshots are freed by a later write once no dispatch is running.
+/// Each handler has its own lock, so a handler that is already running (e.g. in an interrupted
+/// dispatch) is skipped rather than waited for.
+///
+/// Writers do take locks and allocate, so the list must only be changed outside interrupt
+/// context, never from a handler. Debug builds panic when a handler tries.
+pub(in crate::interrupts) struct HandlerList<H> {
+    current: AtomicPtr<Snapshot<H>>,
+    readers: AtomicUsize,
+    retired: Mutex<Retired<H>>,
+}
+
+#[allow(unsafe_code)]
+unsafe impl<H: Send> Sync for HandlerList<H> {}
+
+#[allow(unsafe_code)]
+unsafe impl<H: Send> Send for HandlerList<H> {}
+
+impl<H> HandlerList<H> {
+    pub const fn new() -> Self {
+        Self {
+            current: AtomicPtr::new(ptr::null_mut()),
+            readers: AtomicUsize::new(0),
+            retired: Mutex::new(Retired(Vec::new())),
+        }
+    }
+
+    /// Adds a handler after every handler with the same or a higher priority.
+    pub fn insert(&self, id: u64, priority: u8, handler: H) {
+        let entry = Arc::new_in(Entry { id, priority, handler: Mutex::new(Some(handler)) }, &INTERRUPTS_ARENA);
+        self.update(|snapshot| {
+            let index = snapshot.partition_point(|other| other.priority >= priority);
+            snapshot.insert(index, entry);
+        });
+    }
+
+    /// Removes the handler registered as `id` and returns it. If the handler is running, e.g. in a
+    /// dispatch on another CPU, it is dropped once it finishes instead of being returned.
+    pub fn remove(&self, id: u64) -> Option<H> {
+        let mut removed = None;
+        self.update(|snapshot| {
+            if let Some(index) = snapshot.iter().position(|entry| entry.id == id) {
+                removed = Some(snapshot.remove(index));
+            }
+        });
+        let entry = removed?;
+        let mut handler = entry.handler.try_lock()?;
+        handler.take()
+    }
+
+    /// Calls `f` with each handler in order until it returns `Some`.
+    #[allow(unsafe_code)]
+    pub fn dispatch<R>(&self, mut f: impl FnMut(&mut H) -> Option<R>) -> Option<R> {
+        let _dispatching = Dispatching::enter();
+        // Announce the read before loading the snapshot, so a writer that swaps it out afterwards
+        // sees a reader and keeps the old snapshot alive.
+        self.readers.fetch_add(1, Ordering::SeqCst);
+        let snapshot = self.current.load(Ordering::SeqCst);
+        let mut result = None;
+        if let Some(snapshot) = unsafe { snapshot.as_ref() } {
+            for entry in snapshot.iter() {
+                let mut handler = match entry.handler.try_lock() {
+                    Some(handler) => handler,
+                    None => continue,
+                };
+                if let Some(handler) = handler.as_mut() {
+                    result = f(handler);
+                    if result.is_some() {
+                        break;
+                    }
+                }
+            }
+        }
+        self.readers.fetch_sub(1, Ordering::SeqCst);
+        result
+    }
+
+    #[allow(unsafe_code)]
+    fn update(&self, change: impl FnOnce(&mut Snapshot<H>)) {
+        // The interrupted code may hold the locks taken below, or be halfway through an
+        // allocation.
+        debug_assert!(!Dispatching::is_running(), "handlers can't be added or removed from inside a dispatch");
+        let mut retired = self.retired.lock();
+
+        let old = self.current.load(Ordering::SeqCst);
+        let mut snapshot = Vec::new_in(&INTERRUPTS_ARENA);
+        if let Some(old) = unsafe { old.as_ref() } {
+            snapshot.extend(old.iter().cloned());
+        }
+        change(&mut snapshot);
+        let new = Box::into_raw(Box::new(snapshot));
+
+        self.current.store(new, Ordering::SeqCst);
+        if !old.is_null() {
+            retired.0.push(old);
+        }
+        // Any dispatch that starts from now on sees the new snapshot, so once there are no
+        // readers nothing can still be using a retired one.
+        if self.readers.load(Ordering::SeqCst) == 0 {
+            for snapshot in retired.0.drain(..) {
+                drop(unsafe { Box::from_raw(snapshot) });
+            }
+        }
+    }
+}
+
+impl<H> Drop for HandlerList<H> {
+    #[allow(unsafe_code)]
+    fn drop(&mut self) {
+        let current = *self.current.get_mut();
+        let retired = self.retired.get_mut();
+        for snapshot in retired.0.drain(..).chain(Some(current).filter(|ptr| !ptr.is_null())) {
+            drop(unsafe { Box::from_raw(snapshot) });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicBool;
+    use std::thread;
+
+    #[test]
+    fn dispatches_in_order_until_a_handler_returns_some() {
+        let list = HandlerList::new();
+        list.insert(0, 0, 1);
+        list.insert(1, 0, 2);
+        list.insert(2, 0, 3);
+
+        let mut seen = Vec::new();
+        let result = list.dispatch(|&mut value| {
+            seen.push(value);
+            if value == 2 { Some(value) } else { None }
+        });
+        assert_eq!(result, Some(2));
+        assert_eq!(seen, [1, 2]);
+
+        assert_eq!(list.remove(1), Some(2));
+        assert_eq!(list.remove(1), None);
+        assert_eq!(list.dispatch(|&mut value| if value == 2 { Some(()) } else { None }), None);
+    }
+
+    #[test]
+    fn higher_priorities_run_first() {
+        let list = HandlerList::new();
+        list.insert(0, 1, 'b');
+        list.insert(1, 2, 'a');
+        list.insert(2, 1, 'c');
+        list.insert(3, 0, 'd');
+
+        let mut seen = Vec::new();
+        list.dispatch(|&mut value| {
+            seen.push(value);
+            None::<()>
+        });
+        assert_eq!(seen, ['a', 'b', 'c', 'd']);
+    }
+
+    #[test]
+    fn changes_during_a_dispatch_apply_to_the_next_one() {
+        let list = HandlerList::new();
+        list.insert(0, 0, 0);
+        let mut calls = 0;
+        list.dispatch(|_| {
+            // Made from another CPU, as a handler isn't allowed to.
+            thread::scope(|scope| {
+                scope.spawn(|| {
+                    list.insert(1, 0, 1);
+                    assert!(list.remove(0).is_none(), "a running handler can't be returned");
+                });
+            });
+            calls += 1;
+            None::<()>
+        });
+        assert_eq!(calls, 1);
+
+        let mut seen = Vec::new();
+        list.dispatch(|&mut value| {
+            seen.push(value);
+            None::<()>
+        });
+        assert_eq!(seen, [1]);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "from inside a dispatch")]
+    fn handlers_cant_change_the_handlers() {
+        let list = HandlerList::new();
+        list.insert(0, 0, 0);
+        list.dispatch(|_| {
+            list.insert(1, 0, 1);
+            None::<()>
+        });
+    }
+
+    #[test]
+    fn survives_concurrent_dispatch_and_updates() {
+        let list = Arc::new(HandlerList::new());
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let readers: Vec<_> = (0..2).map(|_| {
+            let list = list.clone();
+            let stop = stop.clone();
+            thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    list.dispatch(|value: &mut u64| {
+                        *value += 1;
+                        None::<()>
+                    });
+                    thread::yield_now();
+                }
+            })
+        }).collect();
+
+        for id in 0..500 {
+            list.insert(id, (id % 3) as u8, 0);
+            if id >= 8 {
+                list.remove(id - 8);
+            }
+        }
+        stop.store(true, Ordering::Relaxed);
+        for reader in readers {
+            reader.join().unwrap();
+        }
+    }
+}
@@ -0,0 +1,259 @@
+mod deferred;
+mod exceptions;
+mod handler_list;
+mod stats;
+
+pub use deferred::*;
+pub use exceptions::*;
+pub use stats::{for_each_interrupt_stats, interrupt_stats, record_spurious, write_interrupt_stats, InterruptStats};
+
+use handler_list::HandlerList;
+use core::sync::atomic::{AtomicU64, Ordering};
+use alloc::boxed::Box;
+use crate::allocators::KernelArena;
+
+/// Number of interrupt lines the kernel can dispatch: every vector above the 32 reserved for
+/// exceptions.
+pub const INTERRUPT_COUNT: usize = 224;
+/// Lines `0..LEGACY_IRQS` are the ISA IRQs, the rest are handed out by `allocate_vector` except
+/// for `Interrupt::SPURIOUS`.
+pub const LEGACY_IRQS: usize = 16;
+
+static HANDLERS: [HandlerList<Handler>; INTERRUPT_COUNT] = [const { HandlerList::new() }; INTERRUPT_COUNT];
+
+/// Lines that have been handed out by `allocate_vector`.
+static ALLOCATED: [AtomicU64; INTERRUPT_COUNT.div_ceil(64)] = [const { AtomicU64::new(0) }; INTERRUPT_COUNT.div_ceil(64)];
+
+/// Handlers are allocated in `allocators::INTERRUPTS_ARENA`, e.g.
+/// `Box::new_in(handler, &INTERRUPTS_ARENA)`.
+pub type Handler = Box<dyn FnMut(Interrupt) -> HandlerResult + Send, &'static KernelArena>;
+
+/// What a handler did with an interrupt, which decides whether the handlers after it run.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
+pub enum HandlerResult {
+    /// The interrupt wasn't for this handler, or it only observed it.
+    PassThrough,
+    /// The handler serviced its device. The rest still run, as another device sharing the line
+    /// may have raised it too.
+    Handled,
+    /// The handler serviced the interrupt and no other handler should see it.
+    Claim,
+}
+
+/// Handlers with a higher priority run first. Handlers with the same priority run in the order
+/// they were registered.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct Priority(pub u8);
+
+impl Priority {
+    /// For debugger hooks and tracing, which need to see every interrupt before a driver claims
+    /// it.
+    pub const HIGHEST: Self = Self(u8::MAX);
+    pub const DRIVER: Self = Self(128);
+    /// For fallbacks that should only run when nothing else claimed the interrupt.
+    pub const LOWEST: Self = Self(0);
+}
+
+/// An interrupt line. The architecture code decides which CPU vector each line arrives on.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct Interrupt(u8);
+
+impl Interrupt {
+    pub const TIMER: Self = Self(0);
+    pub const KEYBOARD: Self = Self(1);
+    /// Connects the slave PIC, never raised on its own.
+    pub const CASCADE: Self = Self(2);
+    pub const COM2: Self = Self(3);
+    pub const COM1: Self = Self(4);
+    pub const LPT2: Self = Self(5);
+    pub const FLOPPY: Self = Self(6);
+    pub const LPT1: Self = Self(7);
+    pub const RTC: Self = Self(8);
+    pub const MOUSE: Self = Self(12);
+    pub const FPU: Self = Self(13);
+    pub const PRIMARY_ATA: Self = Self(14);
+    pub const SECONDARY_ATA: Self = Self(15);
+    /// The last line, kept for interrupt controllers that need somewhere to send interrupts
+    /// nobody raised. Some only allow a vector whose low four bits are all set there, which the
+    /// last vector always satisfies.
+    pub const SPURIOUS: Self = Self((INTERRUPT_COUNT - 1) as u8);
+
+    /// The legacy IRQ line `irq`, if it is below `LEGACY_IRQS`.
+    pub const fn irq(irq: u8) -> Option<Self> {
+        if (irq as usize) < LEGACY_IRQS { Some(Self(irq)) } else { None }
+    }
+
+    pub const fn from_number(number: usize) -> Option<Self> {
+        if number < INTERRUPT_COUNT { Some(Self(number as u8)) } else { None }
+    }
+
+    pub const fn number(self) -> usize {
+        self.0 as usize
+    }
+
+    pub const fn is_legacy(self) -> bool {
+        self.number() < LEGACY_IRQS
+    }
+
+    /// What is usually wired to a legacy IRQ line, or what a reserved line is for.
+    pub const fn name(self) -> Option<&'static str> {
+        Some(match self {
+            Self::TIMER => "timer",
+            Self::KEYBOARD => "keyboard",
+            Self::CASCADE => "cascade",
+            Self::COM2 => "com2",
+            Self::COM1 => "com1",
+            Self::LPT2 => "lpt2",
+            Self::FLOPPY => "floppy",
+            Self::LPT1 => "lpt1",
+            Self::RTC => "rtc",
+            Self::MOUSE => "mouse",
+            Self::FPU => "fpu",
+            Self::PRIMARY_ATA => "ata0",
+            Self::SECONDARY_ATA => "ata1",
+            Self::SPURIOUS => "spurious",
+            _ => return None,
+        })
+    }
+}
+
+/// Reserves a line that isn't wired to a legacy IRQ, e.g. for a device using MSI. Returns `None`
+/// when all of them are taken.
+pub fn allocate_vector() -> Option<Interrupt> {
+    for number in LEGACY_IRQS..Interrupt::SPURIOUS.number() {
+        let bit = 1 << (number % 64);
+        if ALLOCATED[number / 64].fetch_or(bit, Ordering::Relaxed) & bit == 0 {
+            return Some(Interrupt(number as u8));
+        }
+    }
+    None
+}
+
+/// Gives back a line reserved with `allocate_vector`. Its handlers stay registered, so remove
+/// them first.
+pub fn free_vector(interrupt: Interrupt) {
+    assert!(!interrupt.is_legacy() && interrupt != Interrupt::SPURIOUS, "{:?} was not allocated", interrupt);
+    let number = interrupt.number();
+    ALLOCATED[number / 64].fetch_and(!(1 << (number % 64)), Ordering::Relaxed);
+}
+
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq)]
+pub struct HandlerId(u64, Interrupt);
+
+impl HandlerId {
+    fn new(interrupt: Interrupt) -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT_ID.fetch_add(1, Ordering::Relaxed), interrupt)
+    }
+}
+
+/// Must not be called from interrupt context, including from a handler, as it takes locks and
+/// allocates. A handler that wants to change the handlers can `defer` it.
+pub fn set_handler(interrupt: Interrupt, priority: Priority, handler: Handler) -> HandlerId {
+    let id = HandlerId::new(interrupt);
+    HANDLERS[interrupt.number()].insert(id.0, priority.0, handler);
+    id
+}
+
+/// Removes a handler. Returns `None` if the handler is running, in which case it is dropped once
+/// it finishes. Like `set_handler`, must not be called from interrupt context.
+pub fn remove_handler(id: HandlerId) -> Option<Handler> {
+    HANDLERS[id.1.number()].remove(id.0)
+}
+
+/// Runs the handlers for `interrupt` by priority until one of them claims it. Returns whether any
+/// handler handled it. Safe to call from interrupt context, including while other code is adding
+/// or removing handlers.
+pub fn emit_interrupt(interrupt: Interrupt) -> bool {
+    let start = stats::timestamp();
+    let mut handled = false;
+    let mut invoked = 0;
+    let claimed = HANDLERS[interrupt.number()].dispatch(|handler| {
+        invoked += 1;
+        match handler(interrupt) {
+            HandlerResult::PassThrough => None,
+            HandlerResult::Handled => {
+                handled = true;
+                None
+            }
+            HandlerResult::Claim => Some(()),
+        }
+    });
+    stats::record(interrupt, invoked, stats::timestamp().wrapping_sub(start));
+    handled || claimed.is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::allocators::INTERRUPTS_ARENA;
+
+    #[test]
+    fn allocated_vectors_are_distinct_and_not_legacy() {
+        let first = allocate_vector().unwrap();
+        let second = allocate_vector().unwrap();
+        assert_ne!(first, second);
+        assert!(!first.is_legacy() && !second.is_legacy());
+        assert!(first != Interrupt::SPURIOUS && second != Interrupt::SPURIOUS);
+
+        free_vector(first);
+        free_vector(second);
+    }
+
+    #[test]
+    fn handlers_only_see_their_own_line() {
+        use core::sync::atomic::AtomicUsize;
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+        let vector = allocate_vector().unwrap();
+        let id = set_handler(vector, Priority::DRIVER, Box::new_in(move |interrupt| {
+            assert_eq!(interrupt, vector);
+            CALLS.fetch_add(1, Ordering::Relaxed);
+            HandlerResult::Claim
+        }, &INTERRUPTS_ARENA));
+
+        assert!(!emit_interrupt(Interrupt::SECONDARY_ATA));
+        assert!(emit_interrupt(vector));
+        assert_eq!(CALLS.load(Ordering::Relaxed), 1);
+
+        assert!(remove_handler(id).is_some());
+        free_vector(vector);
+    }
+
+    #[test]
+    fn chain_stops_at_a_claim_but_not_at_handled() {
+        use alloc::sync::Arc;
+        use spin::Mutex;
+
+        let vector = allocate_vector().unwrap();
+        let seen = Arc::new(Mutex::new(alloc::vec::Vec::new()));
+        let handler = |name: &'static str, result: HandlerResult| {
+            let seen = seen.clone();
+            Box::new_in(move |_| {
+                seen.lock().push(name);
+                result
+            }, &INTERRUPTS_ARENA) as Handler
+        };
+        let ids = [
+            set_handler(vector, Priority::LOWEST, handler("fallback", HandlerResult::Claim)),
+            set_handler(vector, Priority::DRIVER, handler("first driver", HandlerResult::Handled)),
+            set_handler(vector, Priority::DRIVER, handler("second driver", HandlerResult::Claim)),
+            set_handler(vector, Priority::HIGHEST, handler("logger", HandlerResult::PassThrough)),
+        ];
+
+        assert!(emit_interrupt(vector));
+        assert_eq!(*seen.lock(), ["logger", "first driver", "second driver"]);
+
+        for id in ids {
+            assert!(remove_handler(id).is_some());
+        }
+        free_vector(vector);
+    }
+
+    #[test]
+    fn only_legacy_lines_are_irqs() {
+        assert_eq!(Interrupt::irq(1), Some(Interrupt::KEYBOARD));
+        assert_eq!(Interrupt::irq(LEGACY_IRQS as u8), None);
+        assert_eq!(Interrupt::from_number(INTERRUPT_COUNT), None);
+    }
+}
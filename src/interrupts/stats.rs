@@ -0,0 +1,144 @@
+use super::{Interrupt, INTERRUPT_COUNT};
+use core::fmt;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+static COUNTERS: [LineCounters; INTERRUPT_COUNT] = [const { LineCounters::new() }; INTERRUPT_COUNT];
+
+/// How often an interrupt line fired and how long its handlers took, in TSC cycles.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct InterruptStats {
+    pub interrupt: Interrupt,
+    pub count: u64,
+    pub spurious: u64,
+    pub handlers_invoked: u64,
+    pub min_cycles: u64,
+    pub avg_cycles: u64,
+    pub max_cycles: u64,
+}
+
+struct LineCounters {
+    count: AtomicU64,
+    spurious: AtomicU64,
+    handlers_invoked: AtomicU64,
+    total_cycles: AtomicU64,
+    min_cycles: AtomicU64,
+    max_cycles: AtomicU64,
+}
+
+impl LineCounters {
+    const fn new() -> Self {
+        Self {
+            count: AtomicU64::new(0),
+            spurious: AtomicU64::new(0),
+            handlers_invoked: AtomicU64::new(0),
+            total_cycles: AtomicU64::new(0),
+            min_cycles: AtomicU64::new(u64::MAX),
+            max_cycles: AtomicU64::new(0),
+        }
+    }
+}
+
+/// A timestamp from the CPU's cycle counter, or 0 where there is none.
+#[cfg(target_arch = "x86_64")]
+#[allow(unsafe_code, unused_unsafe)]
+pub(in crate::interrupts) fn timestamp() -> u64 {
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+pub(in crate::interrupts) fn timestamp() -> u64 {
+    0
+}
+
+pub(in crate::interrupts) fn record(interrupt: Interrupt, handlers_invoked: u64, cycles: u64) {
+    let counters = &COUNTERS[interrupt.number()];
+    counters.count.fetch_add(1, Ordering::Relaxed);
+    counters.handlers_invoked.fetch_add(handlers_invoked, Ordering::Relaxed);
+    counters.total_cycles.fetch_add(cycles, Ordering::Relaxed);
+    counters.min_cycles.fetch_min(cycles, Ordering::Relaxed);
+    counters.max_cycles.fetch_max(cycles, Ordering::Relaxed);
+}
+
+/// Counts an interrupt on `interrupt` that the controller raised without a device asking for it.
+/// Called by the architecture code, which is what can tell.
+pub fn record_spurious(interrupt: Interrupt) {
+    COUNTERS[interrupt.number()].spurious.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn interrupt_stats(interrupt: Interrupt) -> InterruptStats {
+    let counters = &COUNTERS[interrupt.number()];
+    let count = counters.count.load(Ordering::Relaxed);
+    InterruptStats {
+        interrupt,
+        count,
+        spurious: counters.spurious.load(Ordering::Relaxed),
+        handlers_invoked: counters.handlers_invoked.load(Ordering::Relaxed),
+        min_cycles: if count == 0 { 0 } else { counters.min_cycles.load(Ordering::Relaxed) },
+        avg_cycles: counters.total_cycles.load(Ordering::Relaxed).checked_div(count).unwrap_or(0),
+        max_cycles: counters.max_cycles.load(Ordering::Relaxed),
+    }
+}
+
+/// Calls `f` with the statistics of every line that has fired at least once, spuriously or not.
+pub fn for_each_interrupt_stats(mut f: impl FnMut(&InterruptStats)) {
+    (0..INTERRUPT_COUNT)
+        .filter_map(Interrupt::from_number)
+        .map(interrupt_stats)
+        .filter(|stats| stats.count != 0 || stats.spurious != 0)
+        .for_each(|stats| f(&stats));
+}
+
+/// Writes a table of every line that has fired to `out`, laid out like `/proc/interrupts`.
+pub fn write_interrupt_stats(out: &mut dyn fmt::Write) -> fmt::Result {
+    writeln!(out, "{:>5} {:>12} {:>10} {:>12} {:>10} {:>10} {:>10}", "LINE", "COUNT", "SPURIOUS", "HANDLERS", "MIN", "AVG", "MAX")?;
+    let mut result = Ok(());
+    for_each_interrupt_stats(|stats| {
+        if result.is_err() {
+            return;
+        }
+        result = writeln!(
+            out,
+            "{:>4}: {:>12} {:>10} {:>12} {:>10} {:>10} {:>10}  {}",
+            stats.interrupt.number(), stats.count, stats.spurious, stats.handlers_invoked,
+            stats.min_cycles, stats.avg_cycles, stats.max_cycles, stats.interrupt.name().unwrap_or("-"),
+        );
+    });
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::allocators::INTERRUPTS_ARENA;
+    use crate::interrupts::{allocate_vector, emit_interrupt, free_vector, remove_handler, set_handler, HandlerResult, Priority};
+    use alloc::boxed::Box;
+    use alloc::string::String;
+
+    #[test]
+    fn counts_interrupts_and_the_handlers_they_ran() {
+        let vector = allocate_vector().unwrap();
+        let ids = [
+            set_handler(vector, Priority::HIGHEST, Box::new_in(|_| HandlerResult::PassThrough, &INTERRUPTS_ARENA)),
+            set_handler(vector, Priority::DRIVER, Box::new_in(|_| HandlerResult::Claim, &INTERRUPTS_ARENA)),
+        ];
+        emit_interrupt(vector);
+        emit_interrupt(vector);
+        record_spurious(vector);
+
+        let stats = interrupt_stats(vector);
+        assert_eq!(stats.count, 2);
+        assert_eq!(stats.spurious, 1);
+        assert_eq!(stats.handlers_invoked, 4);
+        assert!(stats.min_cycles <= stats.avg_cycles && stats.avg_cycles <= stats.max_cycles);
+
+        let mut table = String::new();
+        write_interrupt_stats(&mut table).unwrap();
+        let row = table.lines().find(|line| line.trim_start().starts_with(&alloc::format!("{}:", vector.number()))).unwrap();
+        assert!(row.split_whitespace().nth(1) == Some("2"), "{}", row);
+
+        for id in ids {
+            assert!(remove_handler(id).is_some());
+        }
+        free_vector(vector);
+    }
+}
@@ -1,10 +1,22 @@
 #![feature(allocator_api)]
-#![no_std]
+#![cfg_attr(target_os = "none", feature(alloc_error_handler))]
+#![cfg_attr(not(test), no_std)]
 #![deny(unsafe_code)]
 #![deny(clippy::all)]
 
 extern crate alloc;
 
 pub mod allocators;
+pub mod console;
+pub mod memory;
 pub mod interrupts;
 pub mod drivers;
+pub mod sync;
+pub mod task;
+pub mod time;
+mod event_loop;
+
+pub use event_loop::{
+    register_event_type, CustomEventId, Event, EventFilter, EventHandler, EventHandlerId, EventKind, EventLoop,
+    EventQueueFull, TooManyEventTypes, TooManyHandlers, MAX_CUSTOM_EVENT_TYPES,
+};
@@ -0,0 +1,107 @@
+use super::VirtAddr;
+use core::fmt;
+use spin::Mutex;
+
+/// Tries to make `address` accessible, e.g. by mapping a page on first use. Returns whether the
+/// faulting access can be retried.
+pub type PageFaultResolver = fn(VirtAddr, PageFaultCause) -> bool;
+
+const MAX_RESOLVERS: usize = 8;
+
+static RESOLVERS: Mutex<[Option<PageFaultResolver>; MAX_RESOLVERS]> = Mutex::new([None; MAX_RESOLVERS]);
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct TooManyResolvers;
+
+/// Why a page fault happened, decoded from the error code the CPU pushed for it.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct PageFaultCause {
+    /// The page was mapped but the access wasn't allowed. Otherwise the page wasn't mapped.
+    pub present: bool,
+    pub write: bool,
+    pub user: bool,
+    /// A page table entry had a reserved bit set.
+    pub reserved_bit: bool,
+    pub instruction_fetch: bool,
+}
+
+impl PageFaultCause {
+    pub const fn from_error_code(error_code: u64) -> Self {
+        Self {
+            present: error_code & (1 << 0) != 0,
+            write: error_code & (1 << 1) != 0,
+            user: error_code & (1 << 2) != 0,
+            reserved_bit: error_code & (1 << 3) != 0,
+            instruction_fetch: error_code & (1 << 4) != 0,
+        }
+    }
+}
+
+impl fmt::Display for PageFaultCause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let access = match (self.instruction_fetch, self.write) {
+            (true, _) => "instruction fetch from",
+            (false, true) => "write to",
+            (false, false) => "read from",
+        };
+        let page = if self.present { "a protected" } else { "an unmapped" };
+        let mode = if self.user { "user" } else { "kernel" };
+        write!(f, "{} {} page in {} mode", access, page, mode)?;
+        if self.reserved_bit {
+            write!(f, " (reserved bit set in a page table entry)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Registers a resolver to try when a page fault isn't a stack overflow. Resolvers run in the
+/// page fault handler, so they must not take locks the faulting code might hold.
+pub fn register_page_fault_resolver(resolver: PageFaultResolver) -> Result<(), TooManyResolvers> {
+    let mut resolvers = RESOLVERS.lock();
+    let slot = resolvers.iter_mut().find(|slot| slot.is_none()).ok_or(TooManyResolvers)?;
+    *slot = Some(resolver);
+    Ok(())
+}
+
+/// Asks the registered resolvers in turn to fix a fault at `address`, returning whether one did.
+pub fn resolve_page_fault(address: VirtAddr, cause: PageFaultCause) -> bool {
+    // A fault while the list is being changed can't wait for it.
+    let resolvers = match RESOLVERS.try_lock() {
+        Some(resolvers) => *resolvers,
+        None => return false,
+    };
+    resolvers.iter().flatten().any(|resolver| resolver(address, cause))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn decodes_the_error_code() {
+        let cause = PageFaultCause::from_error_code(0b00111);
+        assert!(cause.present && cause.write && cause.user);
+        assert!(!cause.reserved_bit && !cause.instruction_fetch);
+        assert_eq!(cause.to_string(), "write to a protected page in user mode");
+
+        let cause = PageFaultCause::from_error_code(0b11000);
+        assert_eq!(cause.to_string(), "instruction fetch from an unmapped page in kernel mode (reserved bit set in a page table entry)");
+    }
+
+    #[test]
+    fn resolvers_are_tried_until_one_succeeds() {
+        const ADDRESS: u64 = 0x_dead_0000;
+        fn decline(_: VirtAddr, _: PageFaultCause) -> bool {
+            false
+        }
+        fn accept(address: VirtAddr, _: PageFaultCause) -> bool {
+            address.as_u64() == ADDRESS
+        }
+
+        register_page_fault_resolver(decline).unwrap();
+        register_page_fault_resolver(accept).unwrap();
+        assert!(resolve_page_fault(VirtAddr::new(ADDRESS), PageFaultCause::default()));
+        assert!(!resolve_page_fault(VirtAddr::new(ADDRESS + 1), PageFaultCause::default()));
+    }
+}
@@ -0,0 +1,204 @@
+use z_arch_traits::{FrameAllocator, PhysAddr, PhysFrame, PAGE_SIZE};
+use conquer_once::OnceCell;
+use spin::Mutex;
+
+static FRAME_ALLOCATOR: OnceCell<Mutex<BitmapFrameAllocator>> = OnceCell::uninit();
+
+/// A range of physical memory that the platform reports as usable RAM.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
+pub struct MemoryRegion {
+    start: PhysAddr,
+    end: PhysAddr,
+}
+
+impl MemoryRegion {
+    pub const fn new(start: PhysAddr, end: PhysAddr) -> Self {
+        Self { start, end }
+    }
+
+    pub const fn start(&self) -> PhysAddr {
+        self.start
+    }
+
+    pub const fn end(&self) -> PhysAddr {
+        self.end
+    }
+}
+
+/// Number of `u64` words a bitmap needs to track every frame below `max_address`.
+pub const fn bitmap_words(max_address: u64) -> usize {
+    let frames = max_address.div_ceil(PAGE_SIZE);
+    frames.div_ceil(64) as usize
+}
+
+/// Hands out 4 KiB frames, tracking each one with a bit that is set while the frame is in use.
+///
+/// Frames beyond the end of the bitmap are never handed out, so the size of the bitmap the
+/// platform provides decides how much physical memory the kernel can use.
+pub struct BitmapFrameAllocator {
+    bitmap: &'static mut [u64],
+    next: usize,
+    free_frames: usize,
+    total_frames: usize,
+}
+
+impl BitmapFrameAllocator {
+    pub fn new(regions: impl IntoIterator<Item = MemoryRegion>, bitmap: &'static mut [u64]) -> Self {
+        for word in bitmap.iter_mut() {
+            *word = u64::MAX;
+        }
+        let mut allocator = Self { bitmap, next: 0, free_frames: 0, total_frames: 0 };
+
+        let max_frames = allocator.bitmap.len() as u64 * 64;
+        for region in regions {
+            // Only whole frames are usable, so shrink the region inwards to frame boundaries.
+            let first = region.start.align_up(PAGE_SIZE).as_u64() / PAGE_SIZE;
+            let last = (region.end.align_down(PAGE_SIZE).as_u64() / PAGE_SIZE).min(max_frames);
+            for number in first..last {
+                let (word, bit) = Self::position(number as usize);
+                if allocator.bitmap[word] & bit != 0 {
+                    allocator.bitmap[word] &= !bit;
+                    allocator.free_frames += 1;
+                }
+            }
+        }
+        allocator.total_frames = allocator.free_frames;
+        allocator
+    }
+
+    pub fn free_frames(&self) -> usize {
+        self.free_frames
+    }
+
+    pub fn total_frames(&self) -> usize {
+        self.total_frames
+    }
+
+    fn position(number: usize) -> (usize, u64) {
+        (number / 64, 1 << (number % 64))
+    }
+}
+
+impl FrameAllocator for BitmapFrameAllocator {
+    fn allocate_frame(&mut self) -> Option<PhysFrame> {
+        if self.free_frames == 0 {
+            return None;
+        }
+        let words = self.bitmap.len();
+        for offset in 0..words {
+            let word = (self.next + offset) % words;
+            let used = self.bitmap[word];
+            if used == u64::MAX {
+                continue;
+            }
+            let bit = (!used).trailing_zeros() as usize;
+            self.bitmap[word] |= 1 << bit;
+            self.free_frames -= 1;
+            self.next = word;
+            return Some(PhysFrame::from_number((word * 64 + bit) as u64));
+        }
+        None
+    }
+
+    fn deallocate_frame(&mut self, frame: PhysFrame) {
+        let (word, bit) = Self::position(frame.number() as usize);
+        if word >= self.bitmap.len() {
+            return;
+        }
+        if self.bitmap[word] & bit == 0 {
+            panic!("Frame {:?} was freed twice", frame);
+        }
+        self.bitmap[word] &= !bit;
+        self.free_frames += 1;
+    }
+}
+
+/// Hands frames out of the allocator set up by `init_frame_allocator`.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct GlobalFrameAllocator;
+
+impl FrameAllocator for GlobalFrameAllocator {
+    fn allocate_frame(&mut self) -> Option<PhysFrame> {
+        allocate_frame()
+    }
+
+    fn deallocate_frame(&mut self, frame: PhysFrame) {
+        deallocate_frame(frame)
+    }
+}
+
+pub fn init_frame_allocator(regions: impl IntoIterator<Item = MemoryRegion>, bitmap: &'static mut [u64]) {
+    let allocator = BitmapFrameAllocator::new(regions, bitmap);
+    if FRAME_ALLOCATOR.try_init_once(|| Mutex::new(allocator)).is_err() {
+        panic!("Frame allocator is already initialised");
+    }
+}
+
+pub fn allocate_frame() -> Option<PhysFrame> {
+    FRAME_ALLOCATOR.get()?.lock().allocate_frame()
+}
+
+pub fn deallocate_frame(frame: PhysFrame) {
+    let allocator = FRAME_ALLOCATOR.get().expect("Frame allocator is not initialised");
+    allocator.lock().deallocate_frame(frame);
+}
+
+/// Returns `(free, total)` frame counts, or `None` before the frame allocator is initialised.
+pub fn frame_counts() -> Option<(usize, usize)> {
+    let lock = FRAME_ALLOCATOR.get()?.lock();
+    Some((lock.free_frames(), lock.total_frames()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+    use alloc::vec::Vec;
+
+    fn bitmap(words: usize) -> &'static mut [u64] {
+        Box::leak(alloc::vec![0; words].into_boxed_slice())
+    }
+
+    fn region(start: u64, end: u64) -> MemoryRegion {
+        MemoryRegion::new(PhysAddr::new(start), PhysAddr::new(end))
+    }
+
+    #[test]
+    fn only_hands_out_whole_frames_inside_regions() {
+        let regions = [region(0x1800, 0x5000), region(0x10_000, 0x12_000)];
+        let mut allocator = BitmapFrameAllocator::new(regions.iter().copied(), bitmap(1));
+        assert_eq!(allocator.total_frames(), 5);
+
+        let mut frames: Vec<u64> = core::iter::from_fn(|| allocator.allocate_frame())
+            .map(|frame| frame.start_address().as_u64())
+            .collect();
+        frames.sort_unstable();
+        assert_eq!(frames, [0x2000, 0x3000, 0x4000, 0x10_000, 0x11_000]);
+        assert_eq!(allocator.free_frames(), 0);
+    }
+
+    #[test]
+    fn ignores_frames_beyond_the_bitmap() {
+        let allocator = BitmapFrameAllocator::new([region(0, 128 * PAGE_SIZE)].iter().copied(), bitmap(1));
+        assert_eq!(allocator.total_frames(), 64);
+    }
+
+    #[test]
+    fn reuses_freed_frames() {
+        let mut allocator = BitmapFrameAllocator::new([region(0, 4 * PAGE_SIZE)].iter().copied(), bitmap(1));
+        let frames: Vec<_> = core::iter::from_fn(|| allocator.allocate_frame()).collect();
+        assert_eq!(frames.len(), 4);
+
+        allocator.deallocate_frame(frames[2]);
+        assert_eq!(allocator.allocate_frame(), Some(frames[2]));
+    }
+
+    #[test]
+    #[should_panic(expected = "freed twice")]
+    fn panics_on_double_free() {
+        let mut allocator = BitmapFrameAllocator::new([region(0, PAGE_SIZE)].iter().copied(), bitmap(1));
+        let frame = allocator.allocate_frame().unwrap();
+        allocator.deallocate_frame(frame);
+        allocator.deallocate_frame(frame);
+    }
+}
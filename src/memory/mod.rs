@@ -0,0 +1,10 @@
+mod fault;
+mod frame;
+mod paging;
+mod stack;
+
+pub use fault::*;
+pub use frame::*;
+pub use paging::*;
+pub use stack::*;
+pub use z_arch_traits::{FrameAllocator, MapError, Page, PageFlags, PageMapper, PhysAddr, PhysFrame, VirtAddr, PAGE_SIZE};
@@ -0,0 +1,80 @@
+use super::GlobalFrameAllocator;
+use z_arch_traits::{FrameAllocator, MapError, Page, PageFlags, PageMapper, PhysAddr, PhysFrame, VirtAddr, PAGE_SIZE};
+use conquer_once::OnceCell;
+use spin::Mutex;
+use alloc::boxed::Box;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// Virtual addresses handed out by `map_mmio`.
+pub const MMIO_START: u64 = 0x_5555_0000_0000;
+pub const MMIO_SIZE: u64 = 0x_0001_0000_0000;
+
+static PAGE_MAPPER: OnceCell<Mutex<Box<dyn PageMapper + Send>>> = OnceCell::uninit();
+
+fn page_mapper() -> &'static Mutex<Box<dyn PageMapper + Send>> {
+    PAGE_MAPPER.get().expect("Page mapper is not initialised")
+}
+
+pub fn init_page_mapper(mapper: impl PageMapper + Send + 'static) {
+    if PAGE_MAPPER.try_init_once(|| Mutex::new(Box::new(mapper))).is_err() {
+        panic!("Page mapper is already initialised");
+    }
+}
+
+pub fn map_page(page: Page, frame: PhysFrame, flags: PageFlags) -> Result<(), MapError> {
+    page_mapper().lock().map(page, frame, flags, &mut GlobalFrameAllocator)
+}
+
+/// Maps `page` to a newly allocated frame.
+pub fn map_new_page(page: Page, flags: PageFlags) -> Result<PhysFrame, MapError> {
+    let frame = GlobalFrameAllocator.allocate_frame().ok_or(MapError::FrameAllocationFailed)?;
+    if let Err(err) = map_page(page, frame, flags) {
+        GlobalFrameAllocator.deallocate_frame(frame);
+        return Err(err);
+    }
+    Ok(frame)
+}
+
+/// Unmaps `page`, returning the frame it was mapped to. The frame is not freed.
+pub fn unmap_page(page: Page) -> Result<PhysFrame, MapError> {
+    page_mapper().lock().unmap(page)
+}
+
+pub fn protect_page(page: Page, flags: PageFlags) -> Result<(), MapError> {
+    page_mapper().lock().protect(page, flags)
+}
+
+pub fn translate(addr: VirtAddr) -> Option<PhysAddr> {
+    PAGE_MAPPER.get()?.lock().translate(addr)
+}
+
+/// Maps `size` bytes of device memory starting at `addr` as uncached and returns the virtual
+/// address of `addr`.
+pub fn map_mmio(addr: PhysAddr, size: u64) -> Result<VirtAddr, MapError> {
+    static NEXT_MMIO: AtomicU64 = AtomicU64::new(MMIO_START);
+
+    let first_frame = PhysFrame::containing_address(addr);
+    let offset = addr.as_u64() - first_frame.start_address().as_u64();
+    let pages = (offset + size).div_ceil(PAGE_SIZE);
+
+    let size = pages * PAGE_SIZE;
+    let start = NEXT_MMIO
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |next| {
+            next.checked_add(size).filter(|&end| end <= MMIO_START + MMIO_SIZE)
+        })
+        .map_err(|_| MapError::OutOfAddressSpace)?;
+
+    let first_page = Page::containing_address(VirtAddr::new(start));
+    let flags = PageFlags::WRITABLE | PageFlags::NO_EXECUTE | PageFlags::NO_CACHE;
+    for i in 0..pages {
+        let frame = PhysFrame::from_number(first_frame.number() + i);
+        if let Err(err) = map_page(first_page.offset(i), frame, flags) {
+            // The frames belong to the device, so only the mappings need undoing.
+            for mapped in 0..i {
+                let _ = unmap_page(first_page.offset(mapped));
+            }
+            return Err(err);
+        }
+    }
+    Ok(VirtAddr::new(start + offset))
+}
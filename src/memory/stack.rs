@@ -0,0 +1,80 @@
+use super::{deallocate_frame, map_new_page, unmap_page, MapError, Page, PageFlags, VirtAddr, PAGE_SIZE};
+use core::sync::atomic::{AtomicU64, Ordering};
+use spin::Mutex;
+
+/// Virtual addresses handed out by `allocate_stack`.
+pub const STACKS_START: u64 = 0x_6666_0000_0000;
+pub const STACKS_SIZE: u64 = 0x_0000_1000_0000;
+
+const MAX_STACKS: usize = 32;
+
+static STACKS: Mutex<[Option<KernelStack>; MAX_STACKS]> = Mutex::new([None; MAX_STACKS]);
+
+/// A kernel stack with an unmapped guard page directly below it, so running off the end of the
+/// stack faults instead of silently overwriting whatever comes next.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct KernelStack {
+    name: &'static str,
+    guard: Page,
+    top: VirtAddr,
+}
+
+impl KernelStack {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn guard_page(&self) -> Page {
+        self.guard
+    }
+
+    pub fn bottom(&self) -> VirtAddr {
+        self.guard.offset(1).start_address()
+    }
+
+    pub fn top(&self) -> VirtAddr {
+        self.top
+    }
+}
+
+/// Maps a new `pages` page stack below a guard page and registers it under `name` so faults in
+/// the guard page can be reported as overflows of that stack. Fails with `OutOfAddressSpace` if
+/// the stack region or the table of stacks is full.
+pub fn allocate_stack(name: &'static str, pages: u64) -> Result<KernelStack, MapError> {
+    static NEXT_STACK: AtomicU64 = AtomicU64::new(STACKS_START);
+
+    // Hold the table while mapping, so the slot and the addresses can't be taken by anyone else.
+    let mut stacks = STACKS.lock();
+    let slot = stacks.iter_mut().find(|slot| slot.is_none()).ok_or(MapError::OutOfAddressSpace)?;
+    let start = NEXT_STACK.load(Ordering::Relaxed);
+    let end = (pages + 1).checked_mul(PAGE_SIZE)
+        .and_then(|size| start.checked_add(size))
+        .filter(|&end| end <= STACKS_START + STACKS_SIZE)
+        .ok_or(MapError::OutOfAddressSpace)?;
+
+    let guard = Page::containing_address(VirtAddr::new(start));
+    let flags = PageFlags::WRITABLE | PageFlags::NO_EXECUTE;
+    for i in 1..=pages {
+        if let Err(err) = map_new_page(guard.offset(i), flags) {
+            for mapped in 1..i {
+                if let Ok(frame) = unmap_page(guard.offset(mapped)) {
+                    deallocate_frame(frame);
+                }
+            }
+            return Err(err);
+        }
+    }
+    NEXT_STACK.store(end, Ordering::Relaxed);
+
+    let stack = KernelStack { name, guard, top: guard.offset(pages + 1).start_address() };
+    *slot = Some(stack);
+    Ok(stack)
+}
+
+/// Finds the stack whose guard page contains `addr`. Called from fault handlers, so it gives up
+/// rather than waiting if the stack table is being updated.
+pub fn stack_for_guard(addr: VirtAddr) -> Option<KernelStack> {
+    let page = Page::containing_address(addr);
+    let stacks = STACKS.try_lock()?;
+    stacks.iter().flatten().find(|stack| stack.guard == page).copied()
+}
@@ -0,0 +1,88 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::Waker;
+use spin::Mutex;
+
+/// Holds the waker of a task waiting for something an interrupt handler will do.
+///
+/// `wake` never waits for the lock: if the waiting task is registering at that moment, the wake
+/// is remembered and delivered by `register` instead. A task must register before checking
+/// whether what it waits for has happened, so a wake in between isn't missed.
+pub struct AtomicWaker {
+    waker: Mutex<Option<Waker>>,
+    woken: AtomicBool,
+}
+
+impl AtomicWaker {
+    pub const fn new() -> Self {
+        Self { waker: Mutex::new(None), woken: AtomicBool::new(false) }
+    }
+
+    pub fn register(&self, waker: &Waker) {
+        {
+            let mut slot = self.waker.lock();
+            match slot.as_ref() {
+                Some(current) if current.will_wake(waker) => {}
+                _ => *slot = Some(waker.clone()),
+            }
+        }
+        if self.woken.swap(false, Ordering::AcqRel) {
+            waker.wake_by_ref();
+        }
+    }
+
+    /// Wakes the registered task, if any. Safe to call from interrupt context.
+    pub fn wake(&self) {
+        match self.waker.try_lock() {
+            Some(slot) => {
+                if let Some(waker) = slot.as_ref() {
+                    waker.wake_by_ref();
+                }
+            }
+            None => self.woken.store(true, Ordering::Release),
+        }
+    }
+}
+
+impl Default for AtomicWaker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::sync::Arc;
+    use alloc::task::Wake;
+    use core::sync::atomic::AtomicUsize;
+
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn wakes_the_registered_task_and_remembers_contended_wakes() {
+        let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(counter.clone());
+        let atomic_waker = AtomicWaker::new();
+
+        atomic_waker.wake();
+        atomic_waker.register(&waker);
+        assert_eq!(counter.0.load(Ordering::Relaxed), 0);
+        atomic_waker.wake();
+        assert_eq!(counter.0.load(Ordering::Relaxed), 1);
+
+        // A wake that can't get the lock is handed to the next registration.
+        {
+            let _registering = atomic_waker.waker.lock();
+            atomic_waker.wake();
+        }
+        assert_eq!(counter.0.load(Ordering::Relaxed), 1);
+        atomic_waker.register(&waker);
+        assert_eq!(counter.0.load(Ordering::Relaxed), 2);
+    }
+}
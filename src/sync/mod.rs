@@ -0,0 +1,5 @@
+mod atomic_waker;
+mod ring;
+
+pub use atomic_waker::AtomicWaker;
+pub use ring::Ring;
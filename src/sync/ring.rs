@@ -0,0 +1,188 @@
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+struct Slot<T> {
+    /// Which lap of the ring the slot is on and whether it holds a value, relative to the slot's
+    /// index so that every slot starts out at zero.
+    stamp: AtomicUsize,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+/// A bounded queue that can be pushed to and popped from concurrently without locks, so it can
+/// be shared between interrupt handlers and the code they interrupt.
+///
+/// Each slot carries a stamp saying whether it is ready to be written or read on the current lap,
+/// so producers and consumers only ever race for the head or tail index and never wait for each
+/// other. `N` must be a power of two greater than one.
+pub struct Ring<T, const N: usize> {
+    slots: [Slot<T>; N],
+    head: AtomicUsize,
+    tail: AtomicUsize,
+}
+
+#[allow(unsafe_code)]
+unsafe impl<T: Send, const N: usize> Sync for Ring<T, N> {}
+
+#[allow(unsafe_code)]
+unsafe impl<T: Send, const N: usize> Send for Ring<T, N> {}
+
+impl<T, const N: usize> Ring<T, N> {
+    pub const fn new() -> Self {
+        assert!(N > 1 && N.is_power_of_two(), "ring size must be a power of two greater than one");
+        Self {
+            slots: [const { Slot { stamp: AtomicUsize::new(0), value: UnsafeCell::new(MaybeUninit::uninit()) } }; N],
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    /// Adds `value` to the back of the ring, handing it back if the ring is full.
+    #[allow(unsafe_code)]
+    pub fn push(&self, value: T) -> Result<(), T> {
+        let mut position = self.tail.load(Ordering::Relaxed);
+        loop {
+            let index = position % N;
+            let slot = &self.slots[index];
+            let empty = position.wrapping_sub(index);
+            let stamp = slot.stamp.load(Ordering::Acquire);
+            match stamp.wrapping_sub(empty) as isize {
+                0 => match self.tail.compare_exchange_weak(position, position.wrapping_add(1), Ordering::Relaxed, Ordering::Relaxed) {
+                    Ok(_) => {
+                        unsafe { (*slot.value.get()).write(value) };
+                        slot.stamp.store(empty.wrapping_add(1), Ordering::Release);
+                        return Ok(());
+                    }
+                    Err(current) => position = current,
+                },
+                // The slot still holds the value from the previous lap.
+                diff if diff < 0 => return Err(value),
+                // Another producer got here first.
+                _ => position = self.tail.load(Ordering::Relaxed),
+            }
+        }
+    }
+
+    /// Takes the value at the front of the ring.
+    #[allow(unsafe_code)]
+    pub fn pop(&self) -> Option<T> {
+        let mut position = self.head.load(Ordering::Relaxed);
+        loop {
+            let index = position % N;
+            let slot = &self.slots[index];
+            let full = position.wrapping_sub(index).wrapping_add(1);
+            let stamp = slot.stamp.load(Ordering::Acquire);
+            match stamp.wrapping_sub(full) as isize {
+                0 => match self.head.compare_exchange_weak(position, position.wrapping_add(1), Ordering::Relaxed, Ordering::Relaxed) {
+                    Ok(_) => {
+                        let value = unsafe { (*slot.value.get()).assume_init_read() };
+                        slot.stamp.store(full.wrapping_sub(1).wrapping_add(N), Ordering::Release);
+                        return Some(value);
+                    }
+                    Err(current) => position = current,
+                },
+                // Nothing has been written to the slot on this lap yet.
+                diff if diff < 0 => return None,
+                // Another consumer got here first.
+                _ => position = self.head.load(Ordering::Relaxed),
+            }
+        }
+    }
+
+    /// Whether the ring was empty at the time of the call.
+    pub fn is_empty(&self) -> bool {
+        self.head.load(Ordering::SeqCst) == self.tail.load(Ordering::SeqCst)
+    }
+
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+}
+
+impl<T, const N: usize> Default for Ring<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Drop for Ring<T, N> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+    use std::vec::Vec;
+
+    #[test]
+    fn keeps_order_across_laps() {
+        let ring = Ring::<u32, 4>::new();
+        for lap in 0..10 {
+            for i in 0..4 {
+                ring.push(lap * 4 + i).unwrap();
+            }
+            assert_eq!(ring.push(99), Err(99));
+            for i in 0..4 {
+                assert_eq!(ring.pop(), Some(lap * 4 + i));
+            }
+            assert_eq!(ring.pop(), None);
+            assert!(ring.is_empty());
+        }
+    }
+
+    #[test]
+    fn drops_values_left_in_the_ring() {
+        let value = Arc::new(());
+        let ring = Ring::<_, 8>::new();
+        ring.push(value.clone()).unwrap();
+        ring.push(value.clone()).unwrap();
+        drop(ring);
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+
+    #[test]
+    fn delivers_every_value_from_concurrent_producers() {
+        const PRODUCERS: usize = 4;
+        const PER_PRODUCER: usize = 10_000;
+        let ring = Arc::new(Ring::<usize, 64>::new());
+
+        let producers: Vec<_> = (0..PRODUCERS).map(|producer| {
+            let ring = ring.clone();
+            thread::spawn(move || {
+                for i in 0..PER_PRODUCER {
+                    let mut value = producer * PER_PRODUCER + i;
+                    while let Err(rejected) = ring.push(value) {
+                        value = rejected;
+                        thread::yield_now();
+                    }
+                }
+            })
+        }).collect();
+
+        let mut seen = vec![false; PRODUCERS * PER_PRODUCER];
+        let mut last = [None; PRODUCERS];
+        let mut received = 0;
+        while received < seen.len() {
+            match ring.pop() {
+                Some(value) => {
+                    assert!(!seen[value], "{} was delivered twice", value);
+                    seen[value] = true;
+                    // Values from one producer come out in the order they went in.
+                    let producer = value / PER_PRODUCER;
+                    assert!(last[producer].is_none_or(|last| last < value));
+                    last[producer] = Some(value);
+                    received += 1;
+                }
+                None => thread::yield_now(),
+            }
+        }
+        for producer in producers {
+            producer.join().unwrap();
+        }
+        assert!(ring.is_empty());
+    }
+}
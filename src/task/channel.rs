@@ -0,0 +1,51 @@
+use crate::sync::{AtomicWaker, Ring};
+use core::future::{self, Future};
+use core::task::Poll;
+
+/// The value couldn't be sent because the channel was full.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ChannelFull<T>(pub T);
+
+/// A bounded channel that any number of senders, including interrupt handlers, can send on and a
+/// single task at a time can wait on. `N` must be a power of two.
+pub struct Channel<T, const N: usize> {
+    queue: Ring<T, N>,
+    receiver: AtomicWaker,
+}
+
+impl<T, const N: usize> Channel<T, N> {
+    pub const fn new() -> Self {
+        Self { queue: Ring::new(), receiver: AtomicWaker::new() }
+    }
+
+    /// Sends `value` without waiting. Safe to call from interrupt context.
+    pub fn try_send(&self, value: T) -> Result<(), ChannelFull<T>> {
+        self.queue.push(value).map_err(ChannelFull)?;
+        self.receiver.wake();
+        Ok(())
+    }
+
+    pub fn try_recv(&self) -> Option<T> {
+        self.queue.pop()
+    }
+
+    /// Waits for the next value.
+    pub fn recv(&self) -> impl Future<Output = T> + '_ {
+        future::poll_fn(move |cx| {
+            if let Some(value) = self.queue.pop() {
+                return Poll::Ready(value);
+            }
+            self.receiver.register(cx.waker());
+            match self.queue.pop() {
+                Some(value) => Poll::Ready(value),
+                None => Poll::Pending,
+            }
+        })
+    }
+}
+
+impl<T, const N: usize> Default for Channel<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,108 @@
+use crate::allocators::{KernelArena, EVENT_LOOP_ARENA};
+use crate::sync::Ring;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use core::task::{Context, RawWaker, RawWakerVTable, Waker};
+use spin::Mutex;
+
+/// How many wake-ups can be waiting to be run.
+pub const READY_QUEUE_SIZE: usize = 256;
+
+type TaskFuture = Pin<Box<dyn Future<Output = ()> + Send, &'static KernelArena>>;
+
+/// Tasks that have been woken. Wakers only push their task's id, so waking never allocates or
+/// takes a lock and is safe from interrupt handlers.
+static READY: Ring<TaskId, READY_QUEUE_SIZE> = Ring::new();
+/// Set when a wake-up didn't fit in `READY`, so the next run polls every task.
+static READY_OVERFLOWED: AtomicBool = AtomicBool::new(false);
+static SPAWNED: Mutex<Vec<(TaskId, TaskFuture)>> = Mutex::new(Vec::new());
+static TASKS: Mutex<BTreeMap<TaskId, TaskFuture>> = Mutex::new(BTreeMap::new());
+
+static WAKER_VTABLE: RawWakerVTable = RawWakerVTable::new(clone_waker, wake, wake, drop_waker);
+
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct TaskId(u64);
+
+impl TaskId {
+    fn new() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+fn mark_ready(id: TaskId) {
+    if READY.push(id).is_err() {
+        READY_OVERFLOWED.store(true, Ordering::Release);
+    }
+}
+
+fn clone_waker(data: *const ()) -> RawWaker {
+    RawWaker::new(data, &WAKER_VTABLE)
+}
+
+fn wake(data: *const ()) {
+    mark_ready(TaskId(data as u64));
+}
+
+fn drop_waker(_: *const ()) {}
+
+#[allow(unsafe_code)]
+fn waker(id: TaskId) -> Waker {
+    // The data pointer is the task id, which makes the waker trivially `Send` and `Sync`.
+    unsafe { Waker::from_raw(RawWaker::new(id.0 as *const (), &WAKER_VTABLE)) }
+}
+
+/// Starts running `future` as a task the next time `run_ready` is called. The future is
+/// allocated in `allocators::EVENT_LOOP_ARENA`.
+pub fn spawn(future: impl Future<Output = ()> + Send + 'static) -> TaskId {
+    let id = TaskId::new();
+    SPAWNED.lock().push((id, Box::pin_in(future, &EVENT_LOOP_ARENA)));
+    mark_ready(id);
+    id
+}
+
+/// Polls every task that has been woken since the last call, returning how many were polled.
+/// Meant to be called outside interrupt context, e.g. from the idle loop, and never from a task.
+pub fn run_ready() -> usize {
+    let mut tasks = TASKS.lock();
+    tasks.extend(SPAWNED.lock().drain(..));
+
+    let mut polled = 0;
+    if READY_OVERFLOWED.swap(false, Ordering::AcqRel) {
+        // The lost wake-ups could have been for any task.
+        let ids: Vec<TaskId> = tasks.keys().copied().collect();
+        for id in ids {
+            polled += poll_task(&mut tasks, id) as usize;
+        }
+    }
+    // Tasks that wake themselves go to the back of the queue, so stop after one queue's worth.
+    for _ in 0..READY_QUEUE_SIZE {
+        match READY.pop() {
+            Some(id) => polled += poll_task(&mut tasks, id) as usize,
+            None => break,
+        }
+    }
+    polled
+}
+
+/// Whether `run_ready` has anything to do.
+pub fn has_ready_tasks() -> bool {
+    !READY.is_empty() || READY_OVERFLOWED.load(Ordering::Acquire)
+}
+
+/// Polls the task `id` if it still exists, dropping it once it is done.
+fn poll_task(tasks: &mut BTreeMap<TaskId, TaskFuture>, id: TaskId) -> bool {
+    let future = match tasks.get_mut(&id) {
+        Some(future) => future,
+        None => return false,
+    };
+    let waker = waker(id);
+    if future.as_mut().poll(&mut Context::from_waker(&waker)).is_ready() {
+        tasks.remove(&id);
+    }
+    true
+}
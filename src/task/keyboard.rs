@@ -0,0 +1,41 @@
+use super::channel::{Channel, ChannelFull};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+const SCANCODE_QUEUE_SIZE: usize = 128;
+
+static SCANCODES: Channel<u8, SCANCODE_QUEUE_SIZE> = Channel::new();
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// Hands a scancode to the `ScancodeStream`. Called by the keyboard interrupt handler; the
+/// scancode is dropped if nobody has read the earlier ones.
+pub fn push_scancode(scancode: u8) {
+    if let Err(ChannelFull(_)) = SCANCODES.try_send(scancode) {
+        DROPPED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Scancodes dropped because the queue was full.
+pub fn dropped_scancodes() -> usize {
+    DROPPED.load(Ordering::Relaxed)
+}
+
+/// The scancodes read by the keyboard interrupt handler, in order.
+pub struct ScancodeStream {
+    _private: (),
+}
+
+impl ScancodeStream {
+    /// Returns the stream, or `None` if it has already been taken. There is only one, so that no
+    /// reader misses scancodes another one got.
+    pub fn take() -> Option<Self> {
+        if TAKEN.swap(true, Ordering::AcqRel) {
+            return None;
+        }
+        Some(Self { _private: () })
+    }
+
+    pub async fn next(&mut self) -> u8 {
+        SCANCODES.recv().await
+    }
+}
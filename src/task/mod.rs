@@ -0,0 +1,82 @@
+mod channel;
+mod executor;
+pub mod keyboard;
+pub mod timer;
+
+pub use channel::{Channel, ChannelFull};
+pub use executor::{has_ready_tasks, run_ready, spawn, TaskId, READY_QUEUE_SIZE};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::sync::Arc;
+    use core::sync::atomic::{AtomicU64, Ordering};
+
+    /// Other tests share the executor, so run it until `done` instead of counting polls.
+    fn run_until(done: impl Fn() -> bool) {
+        for _ in 0..1_000 {
+            if done() {
+                return;
+            }
+            run_ready();
+        }
+        panic!("task didn't finish");
+    }
+
+    #[test]
+    fn tasks_wait_for_values_sent_on_a_channel() {
+        static CHANNEL: Channel<u64, 4> = Channel::new();
+        let sum = Arc::new(AtomicU64::new(0));
+        let task_sum = sum.clone();
+        spawn(async move {
+            for _ in 0..3 {
+                task_sum.fetch_add(CHANNEL.recv().await, Ordering::Relaxed);
+            }
+            task_sum.fetch_add(1000, Ordering::Relaxed);
+        });
+
+        run_ready();
+        CHANNEL.try_send(1).unwrap();
+        CHANNEL.try_send(2).unwrap();
+        run_ready();
+        assert_eq!(sum.load(Ordering::Relaxed), 3);
+
+        CHANNEL.try_send(3).unwrap();
+        run_until(|| sum.load(Ordering::Relaxed) == 1006);
+    }
+
+    #[test]
+    fn sleeping_tasks_wake_after_enough_ticks() {
+        let woke_at = Arc::new(AtomicU64::new(0));
+        let task_woke_at = woke_at.clone();
+        let start = crate::time::ticks();
+        spawn(async move {
+            timer::sleep(3).await;
+            task_woke_at.store(crate::time::ticks(), Ordering::Relaxed);
+        });
+
+        run_until(|| {
+            crate::time::tick();
+            woke_at.load(Ordering::Relaxed) != 0
+        });
+        assert!(woke_at.load(Ordering::Relaxed) >= start + 3);
+    }
+
+    #[test]
+    fn timeouts_give_up_on_futures_that_take_too_long() {
+        static NEVER: Channel<u8, 2> = Channel::new();
+        let result = Arc::new(spin::Mutex::new(None));
+        let task_result = result.clone();
+        spawn(async move {
+            let first = timer::timeout(2, async { 1 }).await;
+            let second = timer::timeout(2, NEVER.recv()).await;
+            *task_result.lock() = Some((first, second));
+        });
+
+        run_until(|| {
+            crate::time::tick();
+            result.lock().is_some()
+        });
+        assert_eq!(*result.lock(), Some((Ok(1), Err(timer::TimedOut))));
+    }
+}
@@ -0,0 +1,93 @@
+use crate::sync::AtomicWaker;
+use crate::time;
+use core::future::{self, Future};
+use core::pin::{pin, Pin};
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::task::{Context, Poll};
+
+const MAX_SLEEPERS: usize = 64;
+/// Deadline of a sleeper slot that isn't in use.
+const FREE: u64 = 0;
+
+static SLEEPERS: [Sleeper; MAX_SLEEPERS] = [const { Sleeper { deadline: AtomicU64::new(FREE), waker: AtomicWaker::new() } }; MAX_SLEEPERS];
+
+struct Sleeper {
+    deadline: AtomicU64,
+    waker: AtomicWaker,
+}
+
+/// Wakes the tasks whose sleep is over.
+pub(crate) fn wake_sleepers(now: u64) {
+    for sleeper in SLEEPERS.iter() {
+        let deadline = sleeper.deadline.load(Ordering::Acquire);
+        if deadline != FREE && deadline <= now {
+            sleeper.waker.wake();
+        }
+    }
+}
+
+/// Waits until `ticks` more ticks of `time::ticks` have passed.
+pub fn sleep(ticks: u64) -> Sleep {
+    Sleep { deadline: time::ticks().saturating_add(ticks), slot: None }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct TimedOut;
+
+/// Waits for `future`, giving up after `ticks` ticks.
+pub async fn timeout<F: Future>(ticks: u64, future: F) -> Result<F::Output, TimedOut> {
+    let mut future = pin!(future);
+    let mut sleep = sleep(ticks);
+    future::poll_fn(|cx| {
+        if let Poll::Ready(output) = future.as_mut().poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        Pin::new(&mut sleep).poll(cx).map(|()| Err(TimedOut))
+    }).await
+}
+
+pub struct Sleep {
+    deadline: u64,
+    slot: Option<usize>,
+}
+
+impl Sleep {
+    fn release(&mut self) {
+        if let Some(slot) = self.slot.take() {
+            SLEEPERS[slot].deadline.store(FREE, Ordering::Release);
+        }
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if time::ticks() >= self.deadline {
+            self.release();
+            return Poll::Ready(());
+        }
+        if self.slot.is_none() {
+            let deadline = self.deadline;
+            self.slot = SLEEPERS.iter().position(|sleeper| {
+                sleeper.deadline.compare_exchange(FREE, deadline, Ordering::AcqRel, Ordering::Relaxed).is_ok()
+            });
+        }
+        match self.slot {
+            Some(slot) => SLEEPERS[slot].waker.register(cx.waker()),
+            // With every slot taken, keep polling until the deadline instead of waiting for a wake.
+            None => cx.waker().wake_by_ref(),
+        }
+        if time::ticks() >= self.deadline {
+            self.release();
+            return Poll::Ready(());
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
@@ -0,0 +1,98 @@
+mod wheel;
+
+pub use wheel::{TimerCallback, TimerId};
+
+use wheel::{Timer, Timers};
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use spin::Mutex;
+
+/// Ticks per second assumed until the platform calls `set_tick_frequency`.
+pub const DEFAULT_TICK_FREQUENCY: u32 = 100;
+
+static TICKS: AtomicU64 = AtomicU64::new(0);
+static TICK_FREQUENCY: AtomicU32 = AtomicU32::new(DEFAULT_TICK_FREQUENCY);
+
+static TIMERS: Mutex<Timers> = Mutex::new(Timers::new());
+/// The earliest deadline of any timer, so `tick` can tell whether there is anything to run
+/// without taking the lock.
+static NEXT_DEADLINE: AtomicU64 = AtomicU64::new(u64::MAX);
+static EXPIRY_QUEUED: AtomicBool = AtomicBool::new(false);
+
+/// Advances the tick count, wakes sleeping tasks and queues the callbacks of due timers as
+/// deferred work. Called by whatever timer interrupt the platform has.
+pub fn tick() {
+    let now = TICKS.fetch_add(1, Ordering::AcqRel) + 1;
+    crate::task::timer::wake_sleepers(now);
+    if now >= NEXT_DEADLINE.load(Ordering::Acquire) && !EXPIRY_QUEUED.swap(true, Ordering::AcqRel) {
+        // Try again on the next tick if the queue is full.
+        if crate::interrupts::defer(|_| { run_expired_timers(); }, 0).is_err() {
+            EXPIRY_QUEUED.store(false, Ordering::Release);
+        }
+    }
+}
+
+/// Ticks since boot. Never goes backwards.
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Acquire)
+}
+
+pub fn set_tick_frequency(hz: u32) {
+    TICK_FREQUENCY.store(hz.max(1), Ordering::Relaxed);
+}
+
+pub fn tick_frequency() -> u32 {
+    TICK_FREQUENCY.load(Ordering::Relaxed)
+}
+
+/// The number of ticks that last at least `millis` milliseconds.
+pub fn ticks_from_millis(millis: u64) -> u64 {
+    millis.saturating_mul(u64::from(tick_frequency())).div_ceil(1000)
+}
+
+pub fn uptime_millis() -> u64 {
+    ticks() * 1000 / u64::from(tick_frequency())
+}
+
+/// Cancels a timer. Dropping the handle leaves the timer running.
+#[derive(Debug, Eq, PartialEq)]
+pub struct TimerHandle(TimerId);
+
+impl TimerHandle {
+    pub fn id(&self) -> TimerId {
+        self.0
+    }
+
+    /// Stops the timer from firing again. Returns `false` if it already finished.
+    pub fn cancel(self) -> bool {
+        TIMERS.lock().cancel(self.0)
+    }
+}
+
+fn add(deadline: u64, period: Option<u64>, callback: TimerCallback) -> TimerHandle {
+    let id = TimerId::new();
+    TIMERS.lock().insert(Timer { id, deadline, period, callback });
+    NEXT_DEADLINE.fetch_min(deadline, Ordering::AcqRel);
+    TimerHandle(id)
+}
+
+/// Calls `callback` once, `delay` ticks from now. Callbacks run as deferred work, not in the
+/// timer interrupt.
+pub fn add_timer(delay: u64, callback: TimerCallback) -> TimerHandle {
+    add(ticks().saturating_add(delay.max(1)), None, callback)
+}
+
+/// Calls `callback` every `period` ticks, starting `period` ticks from now.
+pub fn add_periodic_timer(period: u64, callback: TimerCallback) -> TimerHandle {
+    let period = period.max(1);
+    add(ticks().saturating_add(period), Some(period), callback)
+}
+
+/// Runs the callbacks of every timer that is due, earliest deadline first. `tick` queues this as
+/// deferred work, so platforms don't need to call it themselves. Returns how many callbacks ran.
+pub fn run_expired_timers() -> usize {
+    EXPIRY_QUEUED.store(false, Ordering::Release);
+    let ran = wheel::run_expired(&TIMERS, ticks());
+    let timers = TIMERS.lock();
+    NEXT_DEADLINE.store(timers.next_deadline().unwrap_or(u64::MAX), Ordering::Release);
+    ran
+}
@@ -0,0 +1,232 @@
+use crate::allocators::KernelArena;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+use spin::Mutex;
+
+/// Timers are allocated in `allocators::EVENT_LOOP_ARENA`, e.g.
+/// `Box::new_in(callback, &EVENT_LOOP_ARENA)`.
+pub type TimerCallback = Box<dyn FnMut() + Send, &'static KernelArena>;
+
+const WHEEL_SLOTS: usize = 256;
+
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct TimerId(u64);
+
+impl TimerId {
+    pub(in crate::time) fn new() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+pub(in crate::time) struct Timer {
+    pub id: TimerId,
+    pub deadline: u64,
+    pub period: Option<u64>,
+    pub callback: TimerCallback,
+}
+
+/// Timers hashed into slots by their deadline, so expiring a tick only looks at the timers that
+/// share its slot instead of all of them.
+struct TimerWheel {
+    slots: [Vec<Timer>; WHEEL_SLOTS],
+    /// Every tick up to this one has been expired.
+    expired_until: u64,
+}
+
+impl TimerWheel {
+    const fn new() -> Self {
+        Self { slots: [const { Vec::new() }; WHEEL_SLOTS], expired_until: 0 }
+    }
+
+    fn insert(&mut self, mut timer: Timer) {
+        // A deadline that has already been expired would wait a full turn of the wheel.
+        timer.deadline = timer.deadline.max(self.expired_until + 1);
+        self.slots[timer.deadline as usize % WHEEL_SLOTS].push(timer);
+    }
+
+    fn remove(&mut self, id: TimerId) -> Option<Timer> {
+        self.slots.iter_mut().find_map(|slot| {
+            let index = slot.iter().position(|timer| timer.id == id)?;
+            Some(slot.swap_remove(index))
+        })
+    }
+
+    fn next_deadline(&self) -> Option<u64> {
+        self.slots.iter().flatten().map(|timer| timer.deadline).min()
+    }
+
+    /// Takes every timer that is due at `now`, earliest deadline first.
+    fn expire(&mut self, now: u64) -> Vec<Timer> {
+        let mut expired = Vec::new();
+        let ticks = now.saturating_sub(self.expired_until).min(WHEEL_SLOTS as u64);
+        for tick in now - ticks + 1..=now {
+            let slot = &mut self.slots[tick as usize % WHEEL_SLOTS];
+            let mut index = 0;
+            while index < slot.len() {
+                if slot[index].deadline <= now {
+                    expired.push(slot.swap_remove(index));
+                } else {
+                    index += 1;
+                }
+            }
+        }
+        self.expired_until = self.expired_until.max(now);
+        expired.sort_by_key(|timer| (timer.deadline, timer.id));
+        expired
+    }
+}
+
+/// The timer wheel along with the timers taken out of it by a run that hasn't finished yet.
+pub(in crate::time) struct Timers {
+    wheel: TimerWheel,
+    /// Expired timers still waiting for or running their callback, and whether they have been
+    /// cancelled since.
+    expiring: Vec<(TimerId, bool)>,
+}
+
+impl Timers {
+    pub const fn new() -> Self {
+        Self { wheel: TimerWheel::new(), expiring: Vec::new() }
+    }
+
+    pub fn insert(&mut self, timer: Timer) {
+        self.wheel.insert(timer);
+    }
+
+    pub fn next_deadline(&self) -> Option<u64> {
+        self.wheel.next_deadline()
+    }
+
+    /// Stops the timer `id` from firing again. Returns `false` if it had already finished or
+    /// been cancelled.
+    pub fn cancel(&mut self, id: TimerId) -> bool {
+        if self.wheel.remove(id).is_some() {
+            return true;
+        }
+        match self.expiring.iter_mut().find(|(expiring, _)| *expiring == id) {
+            Some((_, cancelled)) if !*cancelled => {
+                *cancelled = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn is_cancelled(&self, id: TimerId) -> bool {
+        self.expiring.iter().any(|&(expiring, cancelled)| expiring == id && cancelled)
+    }
+
+    fn finish(&mut self, id: TimerId) -> bool {
+        let index = self.expiring.iter().position(|&(expiring, _)| expiring == id).unwrap();
+        self.expiring.swap_remove(index).1
+    }
+}
+
+/// Runs the callbacks of the timers in `timers` that are due at `now` in deadline order and
+/// reschedules the periodic ones. The lock isn't held while a callback runs, so callbacks can add
+/// and cancel timers. Returns how many callbacks ran.
+pub(in crate::time) fn run_expired(timers: &Mutex<Timers>, now: u64) -> usize {
+    let expired = {
+        let mut timers = timers.lock();
+        let expired = timers.wheel.expire(now);
+        timers.expiring.extend(expired.iter().map(|timer| (timer.id, false)));
+        expired
+    };
+
+    let mut ran = 0;
+    for mut timer in expired {
+        if !timers.lock().is_cancelled(timer.id) {
+            (timer.callback)();
+            ran += 1;
+        }
+        let mut timers = timers.lock();
+        let cancelled = timers.finish(timer.id);
+        if let (Some(period), false) = (timer.period, cancelled) {
+            // A timer that fell behind skips the periods it missed instead of catching up.
+            timer.deadline = timer.deadline.saturating_add(period).max(now + 1);
+            timers.insert(timer);
+        }
+    }
+    ran
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::sync::Arc;
+    use alloc::vec;
+    use crate::allocators::EVENT_LOOP_ARENA;
+
+    type Log = Arc<Mutex<Vec<(&'static str, u64)>>>;
+
+    fn timer(log: &Log, name: &'static str, deadline: u64, period: Option<u64>, now: Arc<AtomicU64>) -> Timer {
+        let log = log.clone();
+        Timer {
+            id: TimerId::new(),
+            deadline,
+            period,
+            callback: Box::new_in(move || log.lock().push((name, now.load(Ordering::Relaxed))), &EVENT_LOOP_ARENA),
+        }
+    }
+
+    fn run_until(timers: &Mutex<Timers>, clock: &AtomicU64, until: u64) {
+        while clock.load(Ordering::Relaxed) < until {
+            let now = clock.fetch_add(1, Ordering::Relaxed) + 1;
+            run_expired(timers, now);
+        }
+    }
+
+    #[test]
+    fn fires_in_deadline_order_and_repeats_periodic_timers() {
+        let timers = Mutex::new(Timers::new());
+        let clock = Arc::new(AtomicU64::new(0));
+        let log = Log::default();
+        timers.lock().insert(timer(&log, "late", 300, None, clock.clone()));
+        timers.lock().insert(timer(&log, "periodic", 2, Some(3), clock.clone()));
+        timers.lock().insert(timer(&log, "early", 4, None, clock.clone()));
+
+        run_until(&timers, &clock, 9);
+        assert_eq!(*log.lock(), vec![("periodic", 2), ("early", 4), ("periodic", 5), ("periodic", 8)]);
+
+        // Deadlines more than a turn of the wheel away only fire once they are due.
+        log.lock().clear();
+        let periodic = timers.lock().wheel.slots.iter().flatten().find(|timer| timer.period.is_some()).unwrap().id;
+        assert!(timers.lock().cancel(periodic));
+        run_until(&timers, &clock, 299);
+        assert!(log.lock().is_empty());
+        run_until(&timers, &clock, 300);
+        assert_eq!(*log.lock(), vec![("late", 300)]);
+        assert_eq!(timers.lock().next_deadline(), None);
+    }
+
+    #[test]
+    fn a_callback_can_cancel_itself_and_timers_due_after_it() {
+        let timers = Arc::new(Mutex::new(Timers::new()));
+        let log = Log::default();
+        let clock = Arc::new(AtomicU64::new(0));
+        // Same deadline as the victim but an earlier id, so it runs first.
+        let canceller_id = TimerId::new();
+        let victim = timer(&log, "victim", 1, None, clock.clone());
+        let victim_id = victim.id;
+        let canceller_timers = timers.clone();
+        let canceller = Timer {
+            id: canceller_id,
+            deadline: 1,
+            period: Some(1),
+            callback: Box::new_in(move || {
+                let mut timers = canceller_timers.lock();
+                assert!(timers.cancel(canceller_id));
+                assert!(timers.cancel(victim_id));
+                assert!(!timers.cancel(victim_id));
+            }, &EVENT_LOOP_ARENA),
+        };
+        timers.lock().insert(canceller);
+        timers.lock().insert(victim);
+
+        run_until(&timers, &clock, 5);
+        assert!(log.lock().is_empty());
+        assert_eq!(timers.lock().next_deadline(), None);
+    }
+}